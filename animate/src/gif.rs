@@ -0,0 +1,128 @@
+use std::{collections::HashMap, io::Write};
+
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u32) {
+        self.acc |= u32::from(code) << self.bits;
+        self.bits += size;
+        while self.bits >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.out.push(self.acc as u8);
+        }
+        self.out
+    }
+}
+
+// variable width LZW as used by GIF, restarting once the table is full
+pub fn lzw(pixels: &[u8], min_size: u32) -> Vec<u8> {
+    let clear = 1u16 << min_size;
+    let mut w = BitWriter {
+        out: vec![],
+        acc: 0,
+        bits: 0,
+    };
+    let mut table = HashMap::new();
+    let mut size = min_size + 1;
+    let mut max = clear + 1;
+    w.write(clear, size);
+
+    let mut curr: Option<u16> = None;
+    for &p in pixels {
+        let c = match curr {
+            None => {
+                curr = Some(u16::from(p));
+                continue;
+            }
+            Some(c) => c,
+        };
+        if let Some(&next) = table.get(&(c, p)) {
+            curr = Some(next);
+            continue;
+        }
+        w.write(c, size);
+        max += 1;
+        table.insert((c, p), max);
+        if u32::from(max) >= 1 << size {
+            size += 1;
+        }
+        if max == 4095 {
+            w.write(clear, size);
+            table.clear();
+            size = min_size + 1;
+            max = clear + 1;
+        }
+        curr = Some(u16::from(p));
+    }
+    if let Some(c) = curr {
+        w.write(c, size);
+        // the decoder adds the entry for the last code only once it reads it,
+        // and may widen for it before reading the end
+        if max > clear + 1 && u32::from(max) + 1 >= 1 << size && size < 12 {
+            size += 1;
+        }
+    }
+    w.write(clear + 1, size);
+    w.finish()
+}
+
+pub struct Encoder<W: Write> {
+    w: W,
+    width: u16,
+    height: u16,
+    depth: u32,
+}
+
+impl<W: Write> Encoder<W> {
+    // the palette is padded to a power of two of at least 4 entries
+    pub fn new(mut w: W, width: u16, height: u16, palette: &[[u8; 3]]) -> std::io::Result<Self> {
+        let depth = usize::BITS - (palette.len().max(4) - 1).leading_zeros();
+        w.write_all(b"GIF89a")?;
+        w.write_all(&width.to_le_bytes())?;
+        w.write_all(&height.to_le_bytes())?;
+        w.write_all(&[0xf0 | (depth - 1) as u8, 0, 0])?;
+        for i in 0..1 << depth {
+            w.write_all(palette.get(i).unwrap_or(&[0; 3]))?;
+        }
+        // loop forever
+        w.write_all(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+        Ok(Self {
+            w,
+            width,
+            height,
+            depth,
+        })
+    }
+
+    // delay in hundredths of a second
+    pub fn frame(&mut self, pixels: &[u8], delay: u16) -> std::io::Result<()> {
+        let w = &mut self.w;
+        w.write_all(b"\x21\xf9\x04\x00")?;
+        w.write_all(&delay.to_le_bytes())?;
+        w.write_all(&[0, 0, 0x2c, 0, 0, 0, 0])?;
+        w.write_all(&self.width.to_le_bytes())?;
+        w.write_all(&self.height.to_le_bytes())?;
+        w.write_all(&[0, self.depth as u8])?;
+        for block in lzw(pixels, self.depth).chunks(255) {
+            w.write_all(&[block.len() as u8])?;
+            w.write_all(block)?;
+        }
+        w.write_all(&[0])
+    }
+
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.w.write_all(&[0x3b])?;
+        Ok(self.w)
+    }
+}
@@ -0,0 +1,335 @@
+use std::{
+    io::{self, Write},
+    path::Path,
+    time::Duration,
+};
+
+mod gif;
+
+pub type Rgb = [u8; 3];
+
+// palette indices, row by row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Frame {
+    pub fn from_fn(width: usize, height: usize, f: impl Fn(usize, usize) -> u8) -> Self {
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            pixels.extend((0..width).map(|x| f(x, y)));
+        }
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+pub fn gradient(from: Rgb, to: Rgb, n: usize) -> Vec<Rgb> {
+    (0..n)
+        .map(|i| {
+            let t = if n > 1 {
+                i as f64 / (n - 1) as f64
+            } else {
+                0.0
+            };
+            let mut c = [0; 3];
+            for k in 0..3 {
+                c[k] = (from[k] as f64 + (to[k] as f64 - from[k] as f64) * t).round() as u8;
+            }
+            c
+        })
+        .collect()
+}
+
+// Frames of one simulation. Frames may differ in size; every output uses a
+// canvas fitting the largest, with smaller frames centered on colour 0.
+pub struct Animation {
+    palette: Vec<Rgb>,
+    frames: Vec<Frame>,
+}
+
+impl Animation {
+    pub fn new(palette: Vec<Rgb>) -> Self {
+        Self {
+            palette,
+            frames: vec![],
+        }
+    }
+
+    pub fn push(&mut self, f: Frame) {
+        self.frames.push(f);
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    fn canvas(&self) -> (usize, usize) {
+        let w = self.frames.iter().map(|f| f.width).max().unwrap_or(0);
+        let h = self.frames.iter().map(|f| f.height).max().unwrap_or(0);
+        (w, h)
+    }
+
+    // the frame centered on the canvas and scaled up by `scale`
+    fn render(&self, f: &Frame, scale: usize) -> Frame {
+        let (w, h) = self.canvas();
+        let (dx, dy) = ((w - f.width) / 2, (h - f.height) / 2);
+        Frame::from_fn(w * scale, h * scale, |x, y| {
+            let (x, y) = (x / scale, y / scale);
+            if x < dx || y < dy || x - dx >= f.width || y - dy >= f.height {
+                0
+            } else {
+                f.pixels[(y - dy) * f.width + x - dx]
+            }
+        })
+    }
+
+    fn colour(&self, idx: u8) -> Rgb {
+        self.palette.get(idx as usize).copied().unwrap_or([0; 3])
+    }
+
+    // redraws every frame in place using 24-bit ANSI colour, two pixel rows
+    // per line of half blocks
+    pub fn play(&self, w: &mut impl Write, fps: f64) -> io::Result<()> {
+        write!(w, "\x1b[2J")?;
+        for f in &self.frames {
+            let f = self.render(f, 1);
+            write!(w, "\x1b[H")?;
+            for y in (0..f.height).step_by(2) {
+                for x in 0..f.width {
+                    let top = self.colour(f.pixels[y * f.width + x]);
+                    let bottom = if y + 1 < f.height {
+                        self.colour(f.pixels[(y + 1) * f.width + x])
+                    } else {
+                        [0; 3]
+                    };
+                    write!(
+                        w,
+                        "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m\u{2580}",
+                        top[0], top[1], top[2], bottom[0], bottom[1], bottom[2]
+                    )?;
+                }
+                writeln!(w, "\x1b[0m")?;
+            }
+            w.flush()?;
+            if fps > 0.0 {
+                std::thread::sleep(Duration::from_secs_f64(1.0 / fps));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn write_ppm(&self, w: &mut impl Write, idx: usize, scale: usize) -> io::Result<()> {
+        let f = self.render(&self.frames[idx], scale);
+        write!(w, "P6\n{} {}\n255\n", f.width, f.height)?;
+        for &p in &f.pixels {
+            w.write_all(&self.colour(p))?;
+        }
+        Ok(())
+    }
+
+    // frame_0000.ppm, frame_0001.ppm, ... in `dir`
+    pub fn write_ppms(&self, dir: &Path, scale: usize) -> io::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        for i in 0..self.frames.len() {
+            let f = std::fs::File::create(dir.join(format!("frame_{:04}.ppm", i)))?;
+            self.write_ppm(&mut io::BufWriter::new(f), i, scale)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_gif(&self, w: &mut impl Write, scale: usize, fps: f64) -> io::Result<()> {
+        let (width, height) = self.canvas();
+        let size = |n: usize| {
+            u16::try_from(n * scale)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "frame too large for gif"))
+        };
+        let mut enc = gif::Encoder::new(w, size(width)?, size(height)?, &self.palette)?;
+        let delay = if fps > 0.0 {
+            (100.0 / fps).round() as u16
+        } else {
+            0
+        };
+        for f in &self.frames {
+            enc.frame(&self.render(f, scale).pixels, delay)?;
+        }
+        enc.finish()?;
+        Ok(())
+    }
+}
+
+// Where an animation goes, from command line flags shared by the days:
+// --play[=fps], --gif=<file>, --ppm=<dir>, --scale=<n>, --fps=<n>, --steps=<n>
+#[derive(Debug, Clone, PartialEq)]
+pub struct Output {
+    pub play: bool,
+    pub gif: Option<String>,
+    pub ppm: Option<String>,
+    pub scale: usize,
+    pub fps: f64,
+    pub steps: Option<usize>,
+}
+
+impl Output {
+    // picks out the animation flags, returning the other arguments untouched
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> (Option<Self>, Vec<String>) {
+        let mut out = Self {
+            play: false,
+            gif: None,
+            ppm: None,
+            scale: 1,
+            fps: 10.0,
+            steps: None,
+        };
+        let mut rest = vec![];
+        for a in args {
+            match a.split_once('=') {
+                None if a == "--play" => out.play = true,
+                Some(("--play", fps)) | Some(("--fps", fps)) if fps.parse::<f64>().is_ok() => {
+                    out.play |= a.starts_with("--play");
+                    out.fps = fps.parse().unwrap();
+                }
+                Some(("--gif", f)) => out.gif = Some(f.to_string()),
+                Some(("--ppm", d)) => out.ppm = Some(d.to_string()),
+                Some(("--scale", s)) if s.parse::<usize>().is_ok() => {
+                    out.scale = s.parse().unwrap()
+                }
+                Some(("--steps", s)) if s.parse::<usize>().is_ok() => {
+                    out.steps = Some(s.parse().unwrap())
+                }
+                _ => rest.push(a),
+            }
+        }
+        let any = out.play || out.gif.is_some() || out.ppm.is_some();
+        (any.then_some(out), rest)
+    }
+
+    pub fn save(&self, a: &Animation) -> io::Result<()> {
+        if let Some(f) = &self.gif {
+            let mut w = io::BufWriter::new(std::fs::File::create(f)?);
+            a.write_gif(&mut w, self.scale, self.fps)?;
+        }
+        if let Some(d) = &self.ppm {
+            a.write_ppms(Path::new(d), self.scale)?;
+        }
+        if self.play {
+            a.play(&mut io::stdout().lock(), self.fps)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a plain GIF LZW decoder to check the encoder against
+    fn unlzw(data: &[u8], min_size: u32) -> Vec<u8> {
+        let clear = 1usize << min_size;
+        let reset = || {
+            (0..clear)
+                .map(|i| vec![i as u8])
+                .chain([vec![], vec![]])
+                .collect()
+        };
+        let mut table: Vec<Vec<u8>> = reset();
+        let (mut size, mut pos) = (min_size + 1, 0);
+        let mut prev: Option<Vec<u8>> = None;
+        let mut out = vec![];
+        loop {
+            let mut code = 0;
+            for b in 0..size as usize {
+                let bit = (data[(pos + b) / 8] >> ((pos + b) % 8)) & 1;
+                code |= (bit as usize) << b;
+            }
+            pos += size as usize;
+            if code == clear {
+                table = reset();
+                size = min_size + 1;
+                prev = None;
+                continue;
+            }
+            if code == clear + 1 {
+                return out;
+            }
+            let entry = match (table.get(code), &prev) {
+                (Some(e), _) => e.clone(),
+                (None, Some(p)) => [&p[..], &p[..1]].concat(),
+                (None, None) => panic!("bad code"),
+            };
+            out.extend(&entry);
+            if let Some(p) = prev {
+                table.push([&p[..], &entry[..1]].concat());
+                if table.len() == 1 << size && size < 12 {
+                    size += 1;
+                }
+            }
+            prev = Some(entry);
+        }
+    }
+
+    #[test]
+    fn test_lzw() {
+        let mut seed = 7u32;
+        let noise: Vec<u8> = (0..20000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (seed >> 16) as u8 % 5
+            })
+            .collect();
+        for pixels in [vec![], vec![3], vec![1; 10000], noise] {
+            assert_eq!(unlzw(&gif::lzw(&pixels, 3), 3), pixels);
+        }
+        // lengths where the last code makes the decoder widen
+        for n in 0..300 {
+            let pixels: Vec<u8> = (0..n).map(|i: usize| (i % 8) as u8).collect();
+            assert_eq!(unlzw(&gif::lzw(&pixels, 3), 3), pixels, "{} pixels", n);
+        }
+    }
+
+    #[test]
+    fn test_animation() {
+        let mut a = Animation::new(gradient([0, 0, 0], [255, 255, 255], 3));
+        assert_eq!(a.palette[1], [128, 128, 128]);
+        a.push(Frame::from_fn(1, 1, |_, _| 2));
+        a.push(Frame::from_fn(3, 2, |x, y| (x + y) as u8 % 3));
+        assert_eq!(a.canvas(), (3, 2));
+        assert_eq!(a.render(&a.frames[0], 1).pixels, vec![0, 2, 0, 0, 0, 0]);
+
+        let mut ppm = vec![];
+        a.write_ppm(&mut ppm, 0, 2).unwrap();
+        assert!(ppm.starts_with(b"P6\n6 4\n255\n"));
+        assert_eq!(ppm.len(), 11 + 6 * 4 * 3);
+        assert_eq!(&ppm[11 + 6..11 + 12], &[255; 6]);
+
+        let mut gif = vec![];
+        a.write_gif(&mut gif, 1, 4.0).unwrap();
+        assert!(gif.starts_with(b"GIF89a\x03\x00\x02\x00\xf1"));
+        assert_eq!(gif.last(), Some(&0x3b));
+
+        let mut tty = vec![];
+        a.play(&mut tty, 0.0).unwrap();
+        assert_eq!(
+            String::from_utf8(tty).unwrap().matches('\u{2580}').count(),
+            6
+        );
+
+        let args = ["--torus", "--play=5", "--gif=a.gif", "--scale=4"].map(String::from);
+        let (out, rest) = Output::from_args(args);
+        let out = out.unwrap();
+        assert_eq!((out.play, out.fps, out.scale), (true, 5.0, 4));
+        assert_eq!(out.gif.as_deref(), Some("a.gif"));
+        assert_eq!(rest, vec!["--torus"]);
+        assert_eq!(Output::from_args(rest).0, None);
+    }
+}
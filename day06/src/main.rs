@@ -1,5 +1,7 @@
 use std::io::BufRead;
 
+mod population;
+
 fn count_fish(curr: &[usize], rate: usize, delay: usize, days: usize) -> u64 {
     let mut buckets = vec![0; delay + rate];
     for c in curr {
@@ -17,7 +19,20 @@ fn count_fish(curr: &[usize], rate: usize, delay: usize, days: usize) -> u64 {
 }
 
 fn main() {
-    for l in std::io::BufReader::new(std::io::stdin()).lines().flatten() {
+    let args: Vec<_> = std::env::args().collect();
+    if args.len() > 1 {
+        // day06 [scenario] [days]: per-day bucket counts as csv
+        let f = std::fs::File::open(&args[1]).unwrap();
+        let mut p = population::Population::parse(std::io::BufReader::new(f)).unwrap();
+        let days = args.get(2).map_or(80, |d| d.parse().unwrap());
+        p.simulate_csv(days, &mut std::io::stdout().lock()).unwrap();
+        return;
+    }
+
+    for l in std::io::BufReader::new(std::io::stdin())
+        .lines()
+        .map_while(Result::ok)
+    {
         let inp = l
             .split(',')
             .filter_map(|s| s.parse().ok())
@@ -38,4 +53,37 @@ mod tests {
         assert_eq!(count_fish(&inp, 7, 9, 80), 5934);
         assert_eq!(count_fish(&inp, 7, 9, 256), 26_984_457_539);
     }
+
+    #[test]
+    fn test_population() {
+        let inp = "species fish rate=7 delay=9\nfish 3,4,3,1,2\n";
+        let mut p = population::Population::parse(inp.as_bytes()).unwrap();
+        for _ in 0..80 {
+            p.step();
+        }
+        assert_eq!(p.total(), 5934);
+
+        let inp = "species a rate=2 delay=3 lifespan=4 spawns=a,b
+species b rate=5 delay=5 lifespan=2
+a 0, 1@3
+b 4@1
+";
+        let mut p = population::Population::parse(inp.as_bytes()).unwrap();
+        let mut csv = vec![];
+        p.simulate_csv(2, &mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "day,species,total,t0,t1,t2,t3,t4
+0,a,2,1,1,0,0,0
+0,b,1,0,0,0,0,1
+1,a,2,0,1,1,0,0
+1,b,1,0,0,0,0,1
+2,a,2,1,1,0,0,0
+2,b,1,0,0,0,1,0
+"
+        );
+
+        assert!(population::Population::parse("species a rate=1\n".as_bytes()).is_err());
+        assert!(population::Population::parse("a 1\n".as_bytes()).is_err());
+    }
 }
@@ -0,0 +1,199 @@
+use std::{collections::HashMap, error::Error, io::BufRead, io::Write};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+pub struct Species {
+    pub name: String,
+    rate: usize,
+    delay: usize,
+    // fish die once their age reaches `lifespan` days
+    lifespan: Option<usize>,
+    spawns: Vec<usize>,
+}
+
+impl Species {
+    fn width(&self) -> usize {
+        self.rate.max(self.delay)
+    }
+
+    fn ages(&self) -> usize {
+        self.lifespan.unwrap_or(1)
+    }
+}
+
+pub struct Population {
+    pub species: Vec<Species>,
+    // buckets[s][age * width + timer]
+    buckets: Vec<Vec<u64>>,
+    pub day: usize,
+}
+
+fn parse_kv(s: &str) -> Result<(&str, &str)> {
+    s.split_once('=')
+        .ok_or_else(|| format!("expected key=value, got {:?}", s).into())
+}
+
+impl Population {
+    // species <name> rate=<n> delay=<n> [lifespan=<n>] [spawns=<name>,...]
+    // <name> <timer>[@<age>],...
+    pub fn parse(r: impl BufRead) -> Result<Self> {
+        let mut species = vec![];
+        let mut spawns = vec![];
+        let mut initial = vec![];
+        for l in r.lines() {
+            let l = l?;
+            let l = l.trim();
+            if l.is_empty() || l.starts_with('#') {
+                continue;
+            }
+            let mut words = l.split_whitespace();
+            let name = words.next().unwrap();
+            if name != "species" {
+                initial.push((name.to_owned(), words.collect::<Vec<_>>().join("")));
+                continue;
+            }
+
+            let name = words.next().ok_or("missing species name")?.to_owned();
+            let (mut rate, mut delay, mut lifespan) = (None, None, None);
+            let mut sp = vec![name.clone()];
+            for w in words {
+                match parse_kv(w)? {
+                    ("rate", v) => rate = Some(v.parse()?),
+                    ("delay", v) => delay = Some(v.parse()?),
+                    ("lifespan", v) => lifespan = Some(v.parse()?),
+                    ("spawns", v) => sp = v.split(',').map(str::to_owned).collect(),
+                    (k, _) => return Err(format!("unknown species option {:?}", k).into()),
+                }
+            }
+            let rate: usize = rate.ok_or_else(|| format!("{}: missing rate", name))?;
+            let delay: usize = delay.ok_or_else(|| format!("{}: missing delay", name))?;
+            if rate == 0 || delay == 0 || lifespan == Some(0) {
+                return Err(format!("{}: rate, delay and lifespan must be positive", name).into());
+            }
+            species.push(Species {
+                name,
+                rate,
+                delay,
+                lifespan,
+                spawns: vec![],
+            });
+            spawns.push(sp);
+        }
+
+        let index: HashMap<_, _> = species
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (s.name.clone(), i))
+            .collect();
+        let lookup = |n: &str| -> Result<usize> {
+            index
+                .get(n)
+                .copied()
+                .ok_or_else(|| format!("unknown species {:?}", n).into())
+        };
+        for (s, sp) in species.iter_mut().zip(spawns) {
+            s.spawns = sp.iter().map(|n| lookup(n)).collect::<Result<_>>()?;
+        }
+
+        let mut buckets: Vec<_> = species
+            .iter()
+            .map(|s| vec![0; s.width() * s.ages()])
+            .collect();
+        for (name, fish) in initial {
+            let i = lookup(&name)?;
+            let s = &species[i];
+            for f in fish.split(',').filter(|f| !f.is_empty()) {
+                let (timer, age) = match f.split_once('@') {
+                    Some((t, a)) => (t.parse::<usize>()?, a.parse::<usize>()?),
+                    None => (f.parse()?, 0),
+                };
+                if timer >= s.width() || age >= s.lifespan.unwrap_or(usize::MAX) {
+                    return Err(format!("{}: fish {:?} out of range", name, f).into());
+                }
+                let age = if s.lifespan.is_some() { age } else { 0 };
+                buckets[i][age * s.width() + timer] += 1;
+            }
+        }
+
+        Ok(Self {
+            species,
+            buckets,
+            day: 0,
+        })
+    }
+
+    pub fn step(&mut self) {
+        let mut births = vec![0; self.species.len()];
+        for (s, buckets) in self.species.iter().zip(self.buckets.iter_mut()) {
+            let w = s.width();
+            let old = std::mem::replace(buckets, vec![0; buckets.len()]);
+            for (i, n) in old.into_iter().enumerate().filter(|(_, n)| *n > 0) {
+                let (age, timer) = (i / w, i % w);
+                if timer == 0 {
+                    for &b in &s.spawns {
+                        births[b] += n;
+                    }
+                }
+                let age = match s.lifespan {
+                    Some(l) if age + 1 >= l => continue,
+                    Some(_) => age + 1,
+                    None => 0,
+                };
+                let timer = if timer == 0 { s.rate - 1 } else { timer - 1 };
+                buckets[age * w + timer] += n;
+            }
+        }
+        for ((s, buckets), n) in self.species.iter().zip(&mut self.buckets).zip(births) {
+            buckets[s.delay - 1] += n;
+        }
+        self.day += 1;
+    }
+
+    // counts per timer value, summed over ages
+    pub fn timers(&self, species: usize) -> Vec<u64> {
+        let w = self.species[species].width();
+        let mut ret = vec![0; w];
+        for (i, n) in self.buckets[species].iter().enumerate() {
+            ret[i % w] += n;
+        }
+        ret
+    }
+
+    pub fn count(&self, species: usize) -> u64 {
+        self.buckets[species].iter().sum()
+    }
+
+    #[allow(dead_code)]
+    pub fn total(&self) -> u64 {
+        self.buckets.iter().flatten().sum()
+    }
+
+    fn write_row(&self, w: &mut impl Write, width: usize) -> std::io::Result<()> {
+        for (i, s) in self.species.iter().enumerate() {
+            let mut timers = self.timers(i);
+            timers.resize(width, 0);
+            write!(w, "{},{},{}", self.day, s.name, self.count(i))?;
+            for t in timers {
+                write!(w, ",{}", t)?;
+            }
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+
+    // one row per species and day: day,species,total,t0,t1,...
+    pub fn simulate_csv(&mut self, days: usize, w: &mut impl Write) -> std::io::Result<()> {
+        let width = self.species.iter().map(Species::width).max().unwrap_or(0);
+        write!(w, "day,species,total")?;
+        for t in 0..width {
+            write!(w, ",t{}", t)?;
+        }
+        writeln!(w)?;
+        self.write_row(w, width)?;
+        for _ in 0..days {
+            self.step();
+            self.write_row(w, width)?;
+        }
+        Ok(())
+    }
+}
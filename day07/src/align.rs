@@ -0,0 +1,141 @@
+use crate::fuel::{self, FuelCost};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Crab {
+    pub pos: Vec<i64>,
+    pub weight: u64,
+}
+
+// whitespace separated crabs, each "x[,y[,z]][*weight]"
+pub fn parse(s: &str) -> Option<Vec<Crab>> {
+    let crabs = s
+        .split_whitespace()
+        .map(|c| {
+            let (pos, weight) = match c.split_once('*') {
+                Some((pos, w)) => (pos, w.parse().ok()?),
+                None => (c, 1),
+            };
+            let pos = pos
+                .split(',')
+                .map(|p| p.parse().ok())
+                .collect::<Option<_>>()?;
+            Some(Crab { pos, weight })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let dim = crabs.first()?.pos.len();
+    crabs.iter().all(|c| c.pos.len() == dim).then_some(crabs)
+}
+
+fn axis(crabs: &[Crab], d: usize) -> Vec<(i64, u64)> {
+    crabs.iter().map(|c| (c.pos[d], c.weight)).collect()
+}
+
+fn weighted_median(mut v: Vec<(i64, u64)>) -> Option<i64> {
+    v.sort_unstable();
+    let total: u64 = v.iter().map(|c| c.1).sum();
+    let mut acc = 0;
+    v.into_iter().find_map(|(p, w)| {
+        acc += w;
+        (acc * 2 >= total).then_some(p)
+    })
+}
+
+fn dim(crabs: &[Crab]) -> usize {
+    crabs.first().map_or(0, |c| c.pos.len())
+}
+
+// Manhattan distance: the axes are independent, so the per-axis weighted
+// medians give an exact optimum.
+pub fn align_l1(crabs: &[Crab]) -> Option<(Vec<i64>, u64)> {
+    let point = (0..dim(crabs))
+        .map(|d| weighted_median(axis(crabs, d)))
+        .collect::<Option<Vec<_>>>()?;
+    let cost = crabs
+        .iter()
+        .map(|c| {
+            let dist: u64 = c.pos.iter().zip(&point).map(|(a, b)| a.abs_diff(*b)).sum();
+            c.weight * dist
+        })
+        .sum();
+    Some((point, cost))
+}
+
+// any fuel cost applied to each axis travelled separately
+pub fn align_separable<C: FuelCost + ?Sized>(crabs: &[Crab], c: &C) -> Option<(Vec<i64>, u64)> {
+    let mut point = vec![];
+    let mut cost = 0;
+    for d in 0..dim(crabs) {
+        let o = fuel::optimize_weighted(&axis(crabs, d), c)?;
+        point.push(*o.positions[0].start());
+        cost += o.cost;
+    }
+    Some((point, cost))
+}
+
+fn euclidean(a: &[f64], b: &[i64]) -> f64 {
+    a.iter()
+        .zip(b)
+        .map(|(a, &b)| (a - b as f64).powi(2))
+        .sum::<f64>()
+        .sqrt()
+}
+
+// Weiszfeld's algorithm, starting from the weighted centroid
+pub fn geometric_median(crabs: &[Crab], eps: f64, max_iter: usize) -> Option<(Vec<f64>, f64)> {
+    let total = crabs.iter().map(|c| c.weight as f64).sum::<f64>();
+    if total == 0.0 {
+        return None;
+    }
+    let cost = |p: &[f64]| {
+        crabs
+            .iter()
+            .map(|c| c.weight as f64 * euclidean(p, &c.pos))
+            .sum::<f64>()
+    };
+
+    let mut p: Vec<f64> = (0..dim(crabs))
+        .map(|d| {
+            crabs
+                .iter()
+                .map(|c| c.weight as f64 * c.pos[d] as f64)
+                .sum::<f64>()
+                / total
+        })
+        .collect();
+    for _ in 0..max_iter {
+        let mut num = vec![0.0; p.len()];
+        let mut den = 0.0;
+        for c in crabs {
+            let dist = euclidean(&p, &c.pos);
+            if dist < eps {
+                // sitting on a crab: step using the others only and let the
+                // cost check below decide whether to stay
+                continue;
+            }
+            let w = c.weight as f64 / dist;
+            for (n, &x) in num.iter_mut().zip(&c.pos) {
+                *n += w * x as f64;
+            }
+            den += w;
+        }
+        if den == 0.0 {
+            break;
+        }
+        let next: Vec<f64> = num.into_iter().map(|n| n / den).collect();
+        let delta = next
+            .iter()
+            .zip(&p)
+            .map(|(a, b)| (a - b).powi(2))
+            .sum::<f64>()
+            .sqrt();
+        if cost(&next) > cost(&p) {
+            break;
+        }
+        p = next;
+        if delta < eps {
+            break;
+        }
+    }
+    let c = cost(&p);
+    Some((p, c))
+}
@@ -0,0 +1,171 @@
+use std::ops::RangeInclusive;
+
+pub trait FuelCost {
+    fn cost(&self, dist: u64) -> u64;
+
+    // non-decreasing marginal cost, which lets `optimize` binary search
+    fn is_convex(&self) -> bool {
+        true
+    }
+}
+
+pub struct Linear;
+
+impl FuelCost for Linear {
+    fn cost(&self, dist: u64) -> u64 {
+        dist
+    }
+}
+
+pub struct Triangular;
+
+impl FuelCost for Triangular {
+    fn cost(&self, dist: u64) -> u64 {
+        dist * (dist + 1) / 2
+    }
+}
+
+pub struct Quadratic;
+
+impl FuelCost for Quadratic {
+    fn cost(&self, dist: u64) -> u64 {
+        dist * dist
+    }
+}
+
+pub struct Capped<C> {
+    pub inner: C,
+    pub cap: u64,
+}
+
+impl<C: FuelCost> FuelCost for Capped<C> {
+    fn cost(&self, dist: u64) -> u64 {
+        self.inner.cost(dist).min(self.cap)
+    }
+
+    fn is_convex(&self) -> bool {
+        false
+    }
+}
+
+// cost grows by `slope` per step from `start` onwards, until the next segment
+pub struct Piecewise {
+    segments: Vec<(u64, u64)>,
+}
+
+impl Piecewise {
+    pub fn new(mut segments: Vec<(u64, u64)>) -> Self {
+        segments.sort_unstable();
+        Self { segments }
+    }
+
+    // "start:slope,start:slope,..."
+    pub fn parse(s: &str) -> Option<Self> {
+        let segments = s
+            .split(',')
+            .map(|seg| {
+                let (start, slope) = seg.split_once(':')?;
+                Some((start.trim().parse().ok()?, slope.trim().parse().ok()?))
+            })
+            .collect::<Option<_>>()?;
+        Some(Self::new(segments))
+    }
+}
+
+impl FuelCost for Piecewise {
+    fn cost(&self, dist: u64) -> u64 {
+        let mut total = 0;
+        for (i, &(start, slope)) in self.segments.iter().enumerate() {
+            if dist <= start {
+                break;
+            }
+            let end = self.segments.get(i + 1).map_or(dist, |s| s.0.min(dist));
+            total += (end - start) * slope;
+        }
+        total
+    }
+
+    fn is_convex(&self) -> bool {
+        self.segments.windows(2).all(|w| w[0].1 <= w[1].1)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Optimum {
+    pub cost: u64,
+    pub positions: Vec<RangeInclusive<i64>>,
+}
+
+fn total_cost<C: FuelCost + ?Sized>(crabs: &[(i64, u64)], c: &C, t: i64) -> u64 {
+    crabs.iter().map(|&(p, w)| w * c.cost(p.abs_diff(t))).sum()
+}
+
+// smallest x in [lo, hi] for which pred holds, assuming pred is monotonic
+fn partition_point(mut lo: i64, mut hi: i64, pred: impl Fn(i64) -> bool) -> i64 {
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if pred(mid) {
+            hi = mid;
+        } else {
+            lo = mid + 1;
+        }
+    }
+    lo
+}
+
+pub fn optimize<C: FuelCost + ?Sized>(crabs: &[i64], c: &C) -> Option<Optimum> {
+    let crabs: Vec<_> = crabs.iter().map(|&p| (p, 1)).collect();
+    optimize_weighted(&crabs, c)
+}
+
+// crabs are (position, count) pairs. Every optimum lies within [min, max] of
+// the crabs since costs never shrink with distance, so only tied positions
+// within that range are reported.
+pub fn optimize_weighted<C: FuelCost + ?Sized>(crabs: &[(i64, u64)], c: &C) -> Option<Optimum> {
+    let lo = crabs.iter().map(|c| c.0).min()?;
+    let hi = crabs.iter().map(|c| c.0).max()?;
+    let f = |t| total_cost(crabs, c, t);
+
+    if c.is_convex() {
+        let first = partition_point(lo, hi, |t| t == hi || f(t + 1) >= f(t));
+        let cost = f(first);
+        let last = partition_point(first, hi, |t| t == hi || f(t + 1) > cost);
+        return Some(Optimum {
+            cost,
+            positions: vec![first..=last],
+        });
+    }
+
+    let mut best = Optimum {
+        cost: u64::MAX,
+        positions: vec![],
+    };
+    for t in lo..=hi {
+        let cost = f(t);
+        if cost < best.cost {
+            best.cost = cost;
+            best.positions.clear();
+        }
+        if cost == best.cost {
+            match best.positions.last_mut() {
+                Some(r) if *r.end() + 1 == t => *r = *r.start()..=t,
+                _ => best.positions.push(t..=t),
+            }
+        }
+    }
+    Some(best)
+}
+
+pub fn parse_cost(s: &str) -> Option<Box<dyn FuelCost>> {
+    Some(match s.split_once('=') {
+        None if s == "linear" => Box::new(Linear),
+        None if s == "triangular" => Box::new(Triangular),
+        None if s == "quadratic" => Box::new(Quadratic),
+        Some(("capped", cap)) => Box::new(Capped {
+            inner: Linear,
+            cap: cap.parse().ok()?,
+        }),
+        Some(("piecewise", segs)) => Box::new(Piecewise::parse(segs)?),
+        _ => return None,
+    })
+}
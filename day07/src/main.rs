@@ -1,5 +1,8 @@
 use std::io::BufRead;
 
+mod align;
+mod fuel;
+
 fn median<T: Copy + std::cmp::Ord>(v: &mut Vec<T>) -> Option<T> {
     match v.len() {
         0 => None,
@@ -45,11 +48,33 @@ fn solve_part2(inp: &[i32]) -> Option<i32> {
 }
 
 fn main() {
+    // day07 [linear|triangular|quadratic|capped=N|piecewise=start:slope,...]
+    // day07 align: weighted crabs in 1 or more dimensions, "x,y*weight ..."
+    let arg = std::env::args().nth(1);
+    let align = arg.as_deref() == Some("align");
+    let cost = arg
+        .filter(|_| !align)
+        .map(|s| fuel::parse_cost(&s).unwrap());
     for l in std::io::BufReader::new(std::io::stdin()).lines().flatten() {
+        if align {
+            let crabs = align::parse(&l).unwrap();
+            println!("l1: {:?}", align::align_l1(&crabs).unwrap());
+            let tri = align::align_separable(&crabs, &fuel::Triangular);
+            println!("triangular: {:?}", tri.unwrap());
+            let euclid = align::geometric_median(&crabs, 1e-9, 10_000);
+            println!("euclidean: {:?}", euclid.unwrap());
+            continue;
+        }
         let mut inp = l
             .split(',')
             .filter_map(|s| s.parse().ok())
             .collect::<Vec<i32>>();
+        if let Some(cost) = &cost {
+            let crabs: Vec<_> = inp.iter().map(|&p| p as i64).collect();
+            let o = fuel::optimize(&crabs, cost.as_ref()).unwrap();
+            println!("{} at {:?}", o.cost, o.positions);
+            continue;
+        }
         let m = median(&mut inp).unwrap();
         println!("{}", l1_dist(inp.iter(), m).unwrap());
         println!("{}", solve_part2(&inp).unwrap());
@@ -68,4 +93,86 @@ mod tests {
         assert_eq!(l1_dist(inp.iter(), 2).unwrap(), 37);
         assert_eq!(solve_part2(&inp).unwrap(), 168);
     }
+
+    #[test]
+    fn test_fuel_cost() {
+        use fuel::*;
+
+        let crabs = [16, 1, 2, 0, 4, 2, 7, 1, 2, 14];
+        let o = optimize(&crabs, &Linear).unwrap();
+        assert_eq!((o.cost, o.positions), (37, vec![2..=2]));
+        let o = optimize(&crabs, &Triangular).unwrap();
+        assert_eq!((o.cost, o.positions), (168, vec![5..=5]));
+        assert_eq!(optimize(&crabs, &Quadratic).unwrap().positions, vec![5..=5]);
+
+        let o = optimize(&[0, 10], &Linear).unwrap();
+        assert_eq!((o.cost, o.positions), (10, vec![0..=10]));
+        let o = optimize(
+            &[0, 1, 9, 10],
+            &Capped {
+                inner: Linear,
+                cap: 3,
+            },
+        )
+        .unwrap();
+        assert_eq!((o.cost, o.positions), (7, vec![0..=1, 9..=10]));
+
+        let p = Piecewise::new(vec![(0, 1), (2, 3)]);
+        assert!(p.is_convex());
+        assert_eq!((p.cost(1), p.cost(2), p.cost(4)), (1, 2, 8));
+        assert!(!Piecewise::parse("0:2,5:1").unwrap().is_convex());
+        for cost in [
+            "linear",
+            "triangular",
+            "quadratic",
+            "capped=20",
+            "piecewise=0:1,3:4",
+        ] {
+            let cost = parse_cost(cost).unwrap();
+            let o = optimize(&crabs, cost.as_ref()).unwrap();
+            let scan = (0..=16).map(|t| crabs.iter().map(|&p| cost.cost(p.abs_diff(t))).sum());
+            assert_eq!(Some(o.cost), scan.min());
+        }
+        assert!(parse_cost("cubic").is_none());
+        assert!(optimize(&[], &Linear).is_none());
+    }
+
+    #[test]
+    fn test_align() {
+        let crabs = align::parse("16 1 2 0 4 2 7 1 2 14").unwrap();
+        assert_eq!(align::align_l1(&crabs).unwrap(), (vec![2], 37));
+        let tri = align::align_separable(&crabs, &fuel::Triangular).unwrap();
+        assert_eq!(tri, (vec![5], 168));
+        let weighted = align::parse("16 1*2 2*3 0 4 7 14").unwrap();
+        assert_eq!(align::align_l1(&weighted).unwrap(), (vec![2], 37));
+        assert_eq!(
+            align::align_separable(&weighted, &fuel::Triangular),
+            Some(tri)
+        );
+
+        let crabs = align::parse("0,0,0*3 10,0,0 0,10,0 0,0,10").unwrap();
+        assert_eq!(align::align_l1(&crabs).unwrap(), (vec![0, 0, 0], 30));
+
+        // the geometric median of a triangle is its fermat point
+        let crabs = align::parse("0,0 10,0 5,9").unwrap();
+        let (p, cost) = align::geometric_median(&crabs, 1e-9, 10_000).unwrap();
+        assert!((p[0] - 5.0).abs() < 1e-6);
+        let dist = |x: f64, y: f64| -> f64 {
+            [(0.0, 0.0), (10.0, 0.0), (5.0, 9.0)]
+                .iter()
+                .map(|(a, b)| ((x - a).powi(2) + (y - b).powi(2)).sqrt())
+                .sum()
+        };
+        for (dx, dy) in [(-1e-3, 0.0), (1e-3, 0.0), (0.0, -1e-3), (0.0, 1e-3)] {
+            assert!(dist(p[0] + dx, p[1] + dy) >= cost);
+        }
+        // a heavy crab pulls the median onto itself
+        let crabs = align::parse("0,0*10 10,0 0,10").unwrap();
+        let (p, cost) = align::geometric_median(&crabs, 1e-9, 10_000).unwrap();
+        assert!(p[0].abs() < 1e-6 && p[1].abs() < 1e-6);
+        assert!((cost - 20.0).abs() < 1e-6);
+
+        assert!(align::parse("1,2 3").is_none());
+        assert!(align::parse("").is_none());
+    }
 }
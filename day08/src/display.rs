@@ -0,0 +1,317 @@
+use std::{error::Error, fmt, io::BufRead};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+// stop enumerating wirings once this many are known to be consistent
+const MAX_WIRINGS: usize = 16;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SolveError {
+    UnknownWire(String),
+    Unsolvable,
+    // up to MAX_WIRINGS of the consistent wirings
+    Ambiguous(Vec<Vec<usize>>),
+    Undecodable(String),
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownWire(p) => write!(f, "pattern {:?} uses an unknown wire", p),
+            Self::Unsolvable => write!(f, "no wiring matches the patterns"),
+            Self::Ambiguous(w) if w.len() >= MAX_WIRINGS => {
+                write!(f, "at least {} wirings match the patterns", w.len())
+            }
+            Self::Ambiguous(w) => write!(f, "{} wirings match the patterns", w.len()),
+            Self::Undecodable(p) => write!(f, "pattern {:?} is not a glyph", p),
+        }
+    }
+}
+
+impl Error for SolveError {}
+
+// a display whose glyphs are sets of lit segments, segment i being bit i
+pub struct GlyphSet {
+    segments: Vec<char>,
+    glyphs: Vec<(String, u32)>,
+}
+
+const SEVEN: [(&str, &str); 10] = [
+    ("0", "abcefg"),
+    ("1", "cf"),
+    ("2", "acdeg"),
+    ("3", "acdfg"),
+    ("4", "bcdf"),
+    ("5", "abdfg"),
+    ("6", "abdefg"),
+    ("7", "acf"),
+    ("8", "abcdefg"),
+    ("9", "abcdfg"),
+];
+
+const HEX: [(&str, &str); 6] = [
+    ("A", "abcdef"),
+    ("b", "bdefg"),
+    ("C", "abeg"),
+    ("d", "cdefg"),
+    ("E", "abdeg"),
+    ("F", "abde"),
+];
+
+// bits A, B, C, D, E, F, G1, G2, H, J, K, L, M, N
+const FOURTEEN: [(char, u32); 36] = [
+    ('0', 0x0c3f),
+    ('1', 0x0006),
+    ('2', 0x00db),
+    ('3', 0x008f),
+    ('4', 0x00e6),
+    ('5', 0x2069),
+    ('6', 0x00fd),
+    ('7', 0x0007),
+    ('8', 0x00ff),
+    ('9', 0x00ef),
+    ('A', 0x00f7),
+    ('B', 0x128f),
+    ('C', 0x0039),
+    ('D', 0x120f),
+    ('E', 0x00f9),
+    ('F', 0x0071),
+    ('G', 0x00bd),
+    ('H', 0x00f6),
+    ('I', 0x1209),
+    ('J', 0x001e),
+    ('K', 0x2470),
+    ('L', 0x0038),
+    ('M', 0x0536),
+    ('N', 0x2136),
+    ('O', 0x003f),
+    ('P', 0x00f3),
+    ('Q', 0x203f),
+    ('R', 0x20f3),
+    ('S', 0x00ed),
+    ('T', 0x1201),
+    ('U', 0x003e),
+    ('V', 0x0c30),
+    ('W', 0x2836),
+    ('X', 0x2d00),
+    ('Y', 0x1500),
+    ('Z', 0x0c09),
+];
+
+fn letters(n: usize) -> Vec<char> {
+    ('a'..).take(n).collect()
+}
+
+impl GlyphSet {
+    pub fn new(segments: Vec<char>, glyphs: Vec<(String, u32)>) -> Result<Self> {
+        if segments.len() > 32 {
+            return Err("at most 32 segments are supported".into());
+        }
+        for (i, (l, g)) in glyphs.iter().enumerate() {
+            if let Some((other, _)) = glyphs[..i].iter().find(|(_, h)| h == g) {
+                return Err(format!("glyphs {:?} and {:?} are identical", other, l).into());
+            }
+        }
+        Ok(Self { segments, glyphs })
+    }
+
+    fn from_strs<'a>(glyphs: impl Iterator<Item = &'a (&'a str, &'a str)>) -> Self {
+        let mut ret = Self {
+            segments: letters(7),
+            glyphs: vec![],
+        };
+        for (l, s) in glyphs {
+            let g = ret.mask(s).unwrap();
+            ret.glyphs.push((l.to_string(), g));
+        }
+        ret
+    }
+
+    pub fn seven_segment() -> Self {
+        Self::from_strs(SEVEN.iter())
+    }
+
+    pub fn hex_seven_segment() -> Self {
+        Self::from_strs(SEVEN.iter().chain(HEX.iter()))
+    }
+
+    pub fn fourteen_segment() -> Self {
+        Self {
+            segments: letters(14),
+            glyphs: FOURTEEN.iter().map(|&(l, g)| (l.to_string(), g)).collect(),
+        }
+    }
+
+    // the fourteen segment font with the top and bottom bars split in two
+    pub fn sixteen_segment() -> Self {
+        let split = |g: u32| {
+            let mut ret = 0;
+            for (bit, to) in [0b11, 0b100, 0b1000, 0b110000].iter().enumerate() {
+                if g & (1 << bit) != 0 {
+                    ret |= to;
+                }
+            }
+            ret | (g >> 4) << 6
+        };
+        Self {
+            segments: letters(16),
+            glyphs: FOURTEEN
+                .iter()
+                .map(|&(l, g)| (l.to_string(), split(g)))
+                .collect(),
+        }
+    }
+
+    // "segments <names>" followed by "<label> <lit segments>" lines
+    pub fn parse(r: impl BufRead) -> Result<Self> {
+        let mut segments = None;
+        let mut glyphs = vec![];
+        for l in r.lines() {
+            let l = l?;
+            let mut words = l.split_whitespace();
+            let (label, segs) = match (words.next(), words.next()) {
+                (None, _) => continue,
+                (Some(l), _) if l.starts_with('#') => continue,
+                (Some(l), Some(s)) => (l, s),
+                (Some(l), None) => (l, ""),
+            };
+            if label == "segments" && segments.is_none() {
+                segments = Some(Self::new(segs.chars().collect(), vec![])?);
+                continue;
+            }
+            let s = segments.as_ref().ok_or("glyphs before segment names")?;
+            let mask = s
+                .mask(segs)
+                .ok_or_else(|| format!("glyph {:?}: unknown segment in {:?}", label, segs))?;
+            glyphs.push((label.to_string(), mask));
+        }
+        Self::new(segments.ok_or("missing segment names")?.segments, glyphs)
+    }
+
+    pub fn len(&self) -> usize {
+        self.segments.len()
+    }
+
+    fn mask(&self, s: &str) -> Option<u32> {
+        s.chars().try_fold(0, |m, c| {
+            Some(m | 1 << self.segments.iter().position(|&n| n == c)?)
+        })
+    }
+
+    pub fn glyph(&self, mask: u32) -> Option<&str> {
+        self.glyphs
+            .iter()
+            .find(|&&(_, g)| g == mask)
+            .map(|(l, _)| l.as_str())
+    }
+
+    // wires are named like the segments; wiring[w] is the segment wire w drives
+    #[allow(dead_code)]
+    pub fn solve(&self, patterns: &[String]) -> std::result::Result<Vec<usize>, SolveError> {
+        let mut found = self.wirings(patterns, MAX_WIRINGS)?;
+        match found.len() {
+            0 => Err(SolveError::Unsolvable),
+            1 => Ok(found.pop().unwrap()),
+            _ => Err(SolveError::Ambiguous(found)),
+        }
+    }
+
+    // every wiring consistent with the patterns, up to `limit` of them
+    pub fn wirings(
+        &self,
+        patterns: &[String],
+        limit: usize,
+    ) -> std::result::Result<Vec<Vec<usize>>, SolveError> {
+        let patterns = patterns
+            .iter()
+            .map(|p| {
+                self.mask(p)
+                    .ok_or_else(|| SolveError::UnknownWire(p.clone()))
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let mut wiring = vec![usize::MAX; self.len()];
+        let mut found = vec![];
+        self.search(&patterns, 0, &mut wiring, &mut found, limit);
+        Ok(found)
+    }
+
+    // assigns wires in order; a partial wiring survives as long as every
+    // pattern still agrees with some glyph of the same size on the wires
+    // assigned so far
+    fn search(
+        &self,
+        patterns: &[u32],
+        wire: usize,
+        wiring: &mut Vec<usize>,
+        found: &mut Vec<Vec<usize>>,
+        limit: usize,
+    ) {
+        if found.len() >= limit {
+            return;
+        }
+        if wire == self.len() {
+            found.push(wiring.clone());
+            return;
+        }
+        let assigned = (1u32 << wire) - 1;
+        let used = wiring[..wire].iter().fold(0, |m, &s| m | 1 << s);
+        for seg in 0..self.len() {
+            if used & 1 << seg != 0 {
+                continue;
+            }
+            wiring[wire] = seg;
+            let wires = assigned | 1 << wire;
+            let segs = used | 1 << seg;
+            let consistent = patterns.iter().all(|&p| {
+                let image = Self::map(p & wires, wiring);
+                self.glyphs
+                    .iter()
+                    .any(|&(_, g)| g.count_ones() == p.count_ones() && g & segs == image)
+            });
+            if consistent {
+                self.search(patterns, wire + 1, wiring, found, limit);
+            }
+        }
+        wiring[wire] = usize::MAX;
+    }
+
+    fn map(wires: u32, wiring: &[usize]) -> u32 {
+        wiring
+            .iter()
+            .enumerate()
+            .filter(|&(w, _)| wires & 1 << w != 0)
+            .fold(0, |m, (_, &s)| m | 1 << s)
+    }
+
+    // the outputs read the same under every wiring consistent with the
+    // patterns, so an ambiguous wiring is only an error if it changes them
+    pub fn read(
+        &self,
+        patterns: &[String],
+        outputs: &[String],
+    ) -> std::result::Result<String, SolveError> {
+        let found = self.wirings(patterns, MAX_WIRINGS)?;
+        let mut readings = found.iter().map(|w| {
+            outputs
+                .iter()
+                .map(|o| self.decode(o, w))
+                .collect::<std::result::Result<String, _>>()
+        });
+        let first = readings.next().ok_or(SolveError::Unsolvable)??;
+        for r in readings {
+            if r? != first {
+                return Err(SolveError::Ambiguous(found));
+            }
+        }
+        if found.len() >= MAX_WIRINGS {
+            return Err(SolveError::Ambiguous(found));
+        }
+        Ok(first)
+    }
+
+    pub fn decode(&self, word: &str, wiring: &[usize]) -> std::result::Result<&str, SolveError> {
+        self.mask(word)
+            .and_then(|m| self.glyph(Self::map(m, wiring)))
+            .ok_or_else(|| SolveError::Undecodable(word.to_string()))
+    }
+}
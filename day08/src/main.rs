@@ -1,9 +1,10 @@
-use itertools::Itertools;
-use std::{collections::HashSet, io::BufRead};
+use std::io::BufRead;
+
+mod display;
 
 fn parse(f: impl BufRead) -> Vec<(Vec<String>, Vec<String>)> {
     let mut ret = vec![];
-    for l in f.lines().flatten() {
+    for l in f.lines().map_while(Result::ok) {
         let mut l = l.split(" | ");
         let input = l.next();
         if let Some(output) = l.next() {
@@ -28,93 +29,37 @@ fn count_1478(s: &[String]) -> usize {
         .count()
 }
 
-fn char_idx(u: char) -> usize {
-    u as usize - 'a' as usize
-}
-
-fn solve_mapping(all: &[String]) -> [char; 7] {
-    let mut mapping: [HashSet<char>; 7] = Default::default();
-    for i in &mut mapping {
-        i.extend('a'..='g');
-    }
-
-    let mut retain_chars = |base: &str, set: &str| {
-        for c in base.chars() {
-            mapping[char_idx(c)].retain(|&c| set.find(c).is_some());
-        }
-    };
-
-    let mut adg = ('a'..='g').collect::<HashSet<char>>(); // possible candidates for "adg"
-    let mut abfg = ('a'..='g').collect::<HashSet<char>>(); // possible candidates for "abfg"
-    for word in all {
-        match word.len() {
-            2 => retain_chars(word, "cf"),                 // 1
-            4 => retain_chars(word, "bcdf"),               // 4
-            3 => retain_chars(word, "acf"),                // 7
-            5 => adg.retain(|&c| word.find(c).is_some()),  // 2, 3, 5
-            6 => abfg.retain(|&c| word.find(c).is_some()), // 0, 6, 9
-            _ => {}                                        // 8, ignore
-        };
-    }
-    retain_chars(&adg.iter().collect::<String>(), "adg");
-    retain_chars(&abfg.iter().collect::<String>(), "abfg");
-
-    let mut ret = ['\0'; 7];
-    let mut determined = HashSet::new();
-    while determined.len() != ret.len() {
-        for (i, m) in mapping.iter().enumerate() {
-            if m.len() == 1 {
-                let c = *m.iter().next().unwrap();
-                ret[i] = c;
-                determined.insert(c);
+fn main() {
+    // day08 [7seg|hex|14seg|16seg|glyph file]: print the decoded outputs
+    let glyphs = std::env::args().nth(1).map(|a| match a.as_str() {
+        "7seg" => display::GlyphSet::seven_segment(),
+        "hex" => display::GlyphSet::hex_seven_segment(),
+        "14seg" => display::GlyphSet::fourteen_segment(),
+        "16seg" => display::GlyphSet::sixteen_segment(),
+        f => display::GlyphSet::parse(std::io::BufReader::new(std::fs::File::open(f).unwrap()))
+            .unwrap(),
+    });
+    let inp = parse(std::io::BufReader::new(std::io::stdin()));
+    if let Some(glyphs) = glyphs {
+        for (i, (all, out)) in inp.iter().enumerate() {
+            match glyphs.read(all, out) {
+                Ok(s) => println!("{}", s),
+                Err(e) => eprintln!("line {}: {}", i + 1, e),
             }
         }
-
-        for m in &mut mapping {
-            m.retain(|c| determined.get(c).is_none());
-        }
+        return;
     }
-
-    ret
-}
-
-fn get_number(word: &str, mapping: [char; 7]) -> Option<i32> {
-    match word.len() {
-        2 => Some(1),
-        4 => Some(4),
-        3 => Some(7),
-        7 => Some(8),
-        _ => match word
-            .chars()
-            .map(|c| mapping[char_idx(c)])
-            .sorted()
-            .collect::<String>()
-            .as_str()
-        {
-            "abcefg" => Some(0),
-            "acdeg" => Some(2),
-            "acdfg" => Some(3),
-            "abdfg" => Some(5),
-            "abdefg" => Some(6),
-            "abcdfg" => Some(9),
-            _ => None,
-        },
-    }
-}
-
-fn main() {
-    let inp = parse(std::io::BufReader::new(std::io::stdin()));
     println!(
         "{}",
         inp.iter().map(|(_, s)| { count_1478(s) }).sum::<usize>(),
     );
+    let glyphs = display::GlyphSet::seven_segment();
     let mut tot = 0;
-    for (all, out) in inp {
-        let mapping = solve_mapping(&all);
-        tot += out
-            .iter()
-            .map(|s| get_number(s, mapping))
-            .fold(0, |a, v| a * 10 + v.unwrap());
+    for (i, (all, out)) in inp.iter().enumerate() {
+        match glyphs.read(all, out) {
+            Ok(s) => tot += s.parse::<i32>().unwrap(),
+            Err(e) => eprintln!("line {}: {}", i + 1, e),
+        }
     }
     println!("{}", tot);
 }
@@ -131,14 +76,10 @@ mod tests {
             inp.iter().map(|(_, s)| { count_1478(s) }).sum::<usize>(),
             26,
         );
+        let glyphs = display::GlyphSet::seven_segment();
         let mut ret = vec![];
         for (all, out) in inp {
-            let mapping = solve_mapping(&all);
-            ret.push(
-                out.iter()
-                    .map(|s| get_number(s, mapping))
-                    .fold(0, |a, v| a * 10 + v.unwrap()),
-            );
+            ret.push(glyphs.read(&all, &out).unwrap().parse().unwrap());
         }
         assert_eq!(
             ret,
@@ -146,4 +87,86 @@ mod tests {
         );
         assert_eq!(ret.iter().sum::<i32>(), 5353 + 61229);
     }
+
+    // shows every glyph through rotated wires and checks they are recovered
+    fn check_rotated(glyphs: &display::GlyphSet, rotate: usize) -> usize {
+        let n = glyphs.len();
+        let masks: Vec<u32> = (0..1 << n).filter(|&m| glyphs.glyph(m).is_some()).collect();
+        let all: Vec<String> = masks
+            .iter()
+            .map(|m| {
+                (0..n)
+                    .filter(|b| m & 1 << b != 0)
+                    .map(|b| (b'a' + ((b + rotate) % n) as u8) as char)
+                    .collect()
+            })
+            .collect();
+        let wirings = glyphs.wirings(&all, usize::MAX).unwrap();
+        assert!(wirings
+            .iter()
+            .any(|w| (0..n).all(|i| w[(i + rotate) % n] == i)));
+        for wiring in &wirings {
+            for (s, &m) in all.iter().zip(&masks) {
+                assert_eq!(glyphs.decode(s, wiring).ok(), glyphs.glyph(m));
+            }
+        }
+        assert!(glyphs.read(&all, &all).is_ok());
+        wirings.len()
+    }
+
+    #[test]
+    fn test_glyph_sets() {
+        assert_eq!(check_rotated(&display::GlyphSet::hex_seven_segment(), 3), 1);
+        assert_eq!(check_rotated(&display::GlyphSet::fourteen_segment(), 5), 1);
+        // the halves of the top and bottom bars are always lit together
+        assert_eq!(check_rotated(&display::GlyphSet::sixteen_segment(), 7), 4);
+
+        let custom = "segments xyz\n# three bars\nlo x\nmid xy\nhi xyz\n";
+        let glyphs = display::GlyphSet::parse(custom.as_bytes()).unwrap();
+        let wiring = glyphs
+            .solve(&["y".into(), "yz".into(), "xyz".into()])
+            .unwrap();
+        assert_eq!(wiring, vec![2, 0, 1]);
+        assert_eq!(glyphs.decode("zy", &wiring), Ok("mid"));
+        assert!(display::GlyphSet::parse("segments ab\nx a\ny a\n".as_bytes()).is_err());
+        assert!(display::GlyphSet::parse("x a\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_unsolvable() {
+        use display::SolveError;
+
+        let glyphs = display::GlyphSet::seven_segment();
+        let words = |s: &str| s.split(' ').map(String::from).collect::<Vec<_>>();
+        // only 1 and 7 seen: many wirings fit
+        let all = words("ab dab");
+        match glyphs.solve(&all) {
+            Err(SolveError::Ambiguous(w)) => assert_eq!(w.len(), 16),
+            r => panic!("{:?}", r),
+        }
+        assert_eq!(glyphs.wirings(&all, usize::MAX).unwrap().len(), 2 * 24);
+        assert_eq!(
+            glyphs.read(&all, &all[..1]).unwrap_err().to_string(),
+            "at least 16 wirings match the patterns"
+        );
+
+        // two different two-segment patterns can't both be 1
+        assert_eq!(glyphs.solve(&words("ab cd")), Err(SolveError::Unsolvable));
+        assert_eq!(
+            glyphs.solve(&words("ab xyz")),
+            Err(SolveError::UnknownWire("xyz".into()))
+        );
+
+        let f = include_bytes!("../test/input.txt");
+        let (all, _) = &parse(&f[..])[0];
+        let wiring = glyphs.solve(all).unwrap();
+        assert_eq!(
+            glyphs.wirings(all, usize::MAX).unwrap(),
+            vec![wiring.clone()]
+        );
+        assert_eq!(
+            glyphs.decode("abcdefgh", &wiring),
+            Err(SolveError::Undecodable("abcdefgh".into()))
+        );
+    }
 }
@@ -0,0 +1,230 @@
+use std::{collections::VecDeque, io::Write};
+
+struct UnionFind(Vec<usize>);
+
+impl UnionFind {
+    fn new(n: usize) -> Self {
+        Self((0..n).collect())
+    }
+
+    fn find(&mut self, mut i: usize) -> usize {
+        while self.0[i] != i {
+            self.0[i] = self.0[self.0[i]];
+            i = self.0[i];
+        }
+        i
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        self.0[a.max(b)] = a.min(b);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connectivity {
+    Four,
+    Eight,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    // cells at least this high separate basins
+    pub wall: Option<u8>,
+    pub connectivity: Connectivity,
+    // every cell joins the basin its steepest descent leads to
+    pub watershed: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            wall: Some(9),
+            connectivity: Connectivity::Four,
+            watershed: false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Basin {
+    pub size: usize,
+    // lowest cell, first in reading order on ties
+    pub low: (usize, usize),
+    pub height: u8,
+    // (min row, min col, max row, max col)
+    pub bbox: (usize, usize, usize, usize),
+}
+
+pub struct Labels {
+    pub cols: usize,
+    pub labels: Vec<Option<usize>>,
+    pub basins: Vec<Basin>,
+}
+
+const FOUR: [(isize, isize); 4] = [(-1, 0), (0, -1), (0, 1), (1, 0)];
+const EIGHT: [(isize, isize); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+fn neighbours(
+    idx: usize,
+    rows: usize,
+    cols: usize,
+    c: Connectivity,
+) -> impl Iterator<Item = usize> {
+    let (i, j) = ((idx / cols) as isize, (idx % cols) as isize);
+    let offsets: &[_] = match c {
+        Connectivity::Four => &FOUR,
+        Connectivity::Eight => &EIGHT,
+    };
+    offsets.iter().filter_map(move |&(di, dj)| {
+        let (i, j) = (i + di, j + dj);
+        if i >= 0 && j >= 0 && (i as usize) < rows && (j as usize) < cols {
+            Some(i as usize * cols + j as usize)
+        } else {
+            None
+        }
+    })
+}
+
+pub fn label(data: &[u8], rows: usize, cols: usize, cfg: &Config) -> Labels {
+    let open = |idx: usize| cfg.wall.is_none_or(|w| data[idx] < w);
+    let adj = |idx: usize| neighbours(idx, rows, cols, cfg.connectivity).filter(move |&n| open(n));
+    let dist = if cfg.watershed {
+        plateau_distances(data, rows * cols, open, adj)
+    } else {
+        vec![]
+    };
+    let mut uf = UnionFind::new(rows * cols);
+    for idx in (0..rows * cols).filter(|&idx| open(idx)) {
+        if !cfg.watershed {
+            adj(idx).for_each(|n| uf.union(idx, n));
+            continue;
+        }
+        // flow downhill; on a plateau, towards the nearest way down, so a
+        // flat ridge splits between the basins either side of it
+        let mut equal = adj(idx).filter(|&n| data[n] == data[idx]);
+        match (adj(idx).min_by_key(|&n| data[n]), dist[idx]) {
+            (Some(n), _) if data[n] < data[idx] => uf.union(idx, n),
+            (_, Some(d)) => {
+                if let Some(n) = equal.find(|&n| dist[n] == Some(d - 1)) {
+                    uf.union(idx, n);
+                }
+            }
+            // a plateau with no way down is a low point of its own
+            _ => equal.for_each(|n| uf.union(idx, n)),
+        }
+    }
+
+    let mut labels = vec![None; rows * cols];
+    let mut ids = vec![None; rows * cols];
+    let mut basins: Vec<Basin> = vec![];
+    for (idx, &h) in data.iter().enumerate() {
+        if !open(idx) {
+            continue;
+        }
+        let (i, j) = (idx / cols, idx % cols);
+        let root = uf.find(idx);
+        let id = *ids[root].get_or_insert_with(|| {
+            basins.push(Basin {
+                size: 0,
+                low: (i, j),
+                height: h,
+                bbox: (i, j, i, j),
+            });
+            basins.len() - 1
+        });
+        labels[idx] = Some(id);
+
+        let b = &mut basins[id];
+        b.size += 1;
+        if h < b.height {
+            b.low = (i, j);
+            b.height = h;
+        }
+        b.bbox = (b.bbox.0, b.bbox.1.min(j), i, b.bbox.3.max(j));
+    }
+    Labels {
+        cols,
+        labels,
+        basins,
+    }
+}
+
+// For cells on a plateau, how many steps across it they are from a cell
+// with a lower neighbour, that cell being 0. None off plateaus that lead
+// down.
+fn plateau_distances<I: Iterator<Item = usize>>(
+    data: &[u8],
+    len: usize,
+    open: impl Fn(usize) -> bool,
+    adj: impl Fn(usize) -> I,
+) -> Vec<Option<usize>> {
+    let mut dist = vec![None; len];
+    let mut queue: VecDeque<_> = (0..len)
+        .filter(|&idx| open(idx) && adj(idx).any(|n| data[n] < data[idx]))
+        .collect();
+    for &idx in &queue {
+        dist[idx] = Some(0);
+    }
+    while let Some(idx) = queue.pop_front() {
+        let d = dist[idx].map(|d| d + 1);
+        for n in adj(idx) {
+            if data[n] == data[idx] && dist[n].is_none() {
+                dist[n] = d;
+                queue.push_back(n);
+            }
+        }
+    }
+    dist
+}
+
+// Connected areas of equal height with no lower neighbour, reported by
+// their first cell in reading order. A single cell is a plateau of size 1.
+pub fn low_points(data: &[u8], rows: usize, cols: usize, c: Connectivity) -> Vec<usize> {
+    let mut uf = UnionFind::new(rows * cols);
+    let mut lowest = vec![true; rows * cols];
+    for idx in 0..rows * cols {
+        for n in neighbours(idx, rows, cols, c) {
+            if data[n] == data[idx] {
+                uf.union(idx, n);
+            }
+        }
+    }
+    for idx in 0..rows * cols {
+        if neighbours(idx, rows, cols, c).any(|n| data[n] < data[idx]) {
+            let root = uf.find(idx);
+            lowest[root] = false;
+        }
+    }
+    (0..rows * cols)
+        .filter(|&idx| uf.find(idx) == idx && lowest[idx])
+        .collect()
+}
+
+impl Labels {
+    // one row per line of space separated basin ids, '.' for walls
+    pub fn write(&self, w: &mut impl Write) -> std::io::Result<()> {
+        for row in self.labels.chunks(self.cols) {
+            for (j, l) in row.iter().enumerate() {
+                if j > 0 {
+                    write!(w, " ")?;
+                }
+                match l {
+                    Some(id) => write!(w, "{}", id)?,
+                    None => write!(w, ".")?,
+                }
+            }
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+}
@@ -1,102 +1,87 @@
 use std::io::BufRead;
 
+mod basin;
+
 fn parse(f: impl BufRead) -> (Vec<u8>, usize, usize) {
     let mut rows = 0;
     let mut cols = 0;
     let mut ret = vec![];
-    for l in f.lines().flatten() {
+    for l in f.lines().map_while(Result::ok) {
         rows += 1;
-        cols = l.len();
-        ret.extend(l.chars().filter_map(|s| s.to_digit(10).map(|f| f as u8)));
+        let len = ret.len();
+        if l.contains([' ', ',']) {
+            // elevation data: separated heights up to 255
+            ret.extend(l.split([' ', ',']).filter_map(|s| s.parse::<u8>().ok()));
+        } else {
+            ret.extend(l.chars().filter_map(|s| s.to_digit(10).map(|f| f as u8)));
+        }
+        cols = ret.len() - len;
     }
     (ret, rows, cols)
 }
 
-fn flood_fill(
-    data: &[u8],
-    mask: &mut [bool],
-    rows: usize,
-    cols: usize,
-    i: usize,
-    j: usize,
-) -> usize {
-    if data[i * cols + j] == 9 || mask[i * cols + j] {
-        return 0;
-    }
-    mask[i * cols + j] = true;
-    let mut tot = 0;
-    if i > 0 {
-        tot += flood_fill(data, mask, rows, cols, i - 1, j);
-    }
-    if i < rows - 1 {
-        tot += flood_fill(data, mask, rows, cols, i + 1, j);
-    }
-    if j > 0 {
-        tot += flood_fill(data, mask, rows, cols, i, j - 1);
-    }
-    if j < cols - 1 {
-        tot += flood_fill(data, mask, rows, cols, i, j + 1);
+fn find_minima(data: &[u8], rows: usize, cols: usize, mut f: impl FnMut(u8, usize)) {
+    let cfg = basin::Config::default();
+    let labels = basin::label(data, rows, cols, &cfg);
+    // a basin with several low points counts with the first of them only
+    let mut seen = vec![false; labels.basins.len()];
+    for idx in basin::low_points(data, rows, cols, cfg.connectivity) {
+        let size = match labels.labels[idx] {
+            Some(id) if !seen[id] => {
+                seen[id] = true;
+                labels.basins[id].size
+            }
+            _ => 0,
+        };
+        f(data[idx], size);
     }
-    tot + 1
 }
 
-fn find_minima(data: &[u8], rows: usize, cols: usize, mut f: impl FnMut(u8, usize)) {
-    let mut state = vec![true; rows * cols];
+// the product of the three largest basins, of as many as there are
+fn largest(mut basins: Vec<usize>) -> usize {
+    basins.retain(|&b| b > 0);
+    basins.sort_unstable_by(|a, b| b.cmp(a));
+    basins.iter().take(3).product()
+}
 
-    for i in 0..rows {
-        for j in 1..cols {
-            match data[i * cols + j - 1].cmp(&data[i * cols + j]) {
-                std::cmp::Ordering::Less => {
-                    state[i * cols + j] = false;
-                }
-                std::cmp::Ordering::Greater => {
-                    state[i * cols + j - 1] = false;
-                }
-                std::cmp::Ordering::Equal => {
-                    state[i * cols + j] = false;
-                    state[i * cols + j - 1] = false;
-                }
-            }
+fn main() {
+    let (data, rows, cols) = parse(std::io::BufReader::new(std::io::stdin()));
+    // day09 [basins|labels] [--wall=N|--no-wall] [--eight] [--watershed]
+    let mut cfg = basin::Config::default();
+    let mut mode = None;
+    for a in std::env::args().skip(1) {
+        match a.split_once('=') {
+            Some(("--wall", w)) => cfg.wall = Some(w.parse().unwrap()),
+            None if a == "--no-wall" => cfg.wall = None,
+            None if a == "--eight" => cfg.connectivity = basin::Connectivity::Eight,
+            None if a == "--watershed" => cfg.watershed = true,
+            None if !a.starts_with("--") && mode.is_none() => mode = Some(a),
+            _ => panic!("unknown option {}", a),
         }
     }
-    for j in 0..cols {
-        for i in 1..rows {
-            match data[(i - 1) * cols + j].cmp(&data[i * cols + j]) {
-                std::cmp::Ordering::Less => {
-                    state[i * cols + j] = false;
-                }
-                std::cmp::Ordering::Greater => {
-                    state[(i - 1) * cols + j] = false;
-                }
-                std::cmp::Ordering::Equal => {
-                    state[i * cols + j] = false;
-                    state[(i - 1) * cols + j] = false;
-                }
+    match mode.as_deref() {
+        Some("basins") => {
+            let labels = basin::label(&data, rows, cols, &cfg);
+            for (id, b) in labels.basins.iter().enumerate() {
+                println!("{} {:?}", id, b);
             }
+            return;
         }
-    }
-
-    let mut fill = vec![false; rows * cols];
-    for (idx, d) in state.iter().enumerate() {
-        if *d {
-            let cnt = flood_fill(data, &mut fill, rows, cols, idx / cols, idx % cols);
-            f(data[idx], cnt);
+        Some("labels") => {
+            let labels = basin::label(&data, rows, cols, &cfg);
+            labels.write(&mut std::io::stdout().lock()).unwrap();
+            return;
         }
+        _ => {}
     }
-}
-
-fn main() {
-    let (data, rows, cols) = parse(std::io::BufReader::new(std::io::stdin()));
     let mut tot: i32 = 0;
     let mut basins = vec![];
     find_minima(&data, rows, cols, |b, c| {
         tot += i32::from(b) + 1;
         basins.push(c);
     });
-    basins.sort_unstable_by(|a, b| b.cmp(a));
-    let b = basins.iter().take(3).product::<usize>();
     println!("{}", tot);
-    println!("{}", b);
+    println!("{}", largest(basins));
 }
 
 #[cfg(test)]
@@ -118,9 +103,126 @@ mod tests {
             tot += i32::from(b) + 1;
             basins.push(c);
         });
-        basins.sort_unstable_by(|a, b| b.cmp(a));
-        let b = basins.iter().take(3).product::<usize>();
         assert_eq!(tot, 15);
-        assert_eq!(b, 1134);
+        assert_eq!(largest(basins), 1134);
+
+        let labels = basin::label(&data, rows, cols, &basin::Config::default());
+        assert_eq!(labels.basins.len(), 4);
+        assert_eq!(
+            labels.basins[1],
+            basin::Basin {
+                size: 9,
+                low: (0, 9),
+                height: 0,
+                bbox: (0, 5, 2, 9),
+            }
+        );
+        let mut out = vec![];
+        labels.write(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().next(), Some("0 0 . . . 1 1 1 1 1"));
+    }
+
+    #[test]
+    fn test_plateau() {
+        let f = "9999\n9119\n9999\n5555".as_bytes();
+        let (data, rows, cols) = parse(f);
+        let mut minima = vec![];
+        find_minima(&data, rows, cols, |b, c| minima.push((b, c)));
+        assert_eq!(minima, vec![(1, 2), (5, 4)]);
+        assert_eq!(
+            basin::low_points(&data, rows, cols, basin::Connectivity::Four),
+            vec![5, 12]
+        );
+    }
+
+    #[test]
+    fn test_two_low_points() {
+        let (data, rows, cols) = parse("1219\n9999\n3999".as_bytes());
+        let mut minima = vec![];
+        find_minima(&data, rows, cols, |b, c| minima.push((b, c)));
+        assert_eq!(minima, vec![(1, 3), (1, 0), (3, 1)]);
+        assert_eq!(largest(minima.iter().map(|&(_, c)| c).collect()), 3);
+    }
+
+    #[test]
+    fn test_large_basin() {
+        let (rows, cols) = (1000, 3000);
+        let mut data = vec![5; rows * cols];
+        data[rows / 2 * cols + cols / 2] = 0;
+        let labels = basin::label(&data, rows, cols, &basin::Config::default());
+        assert_eq!(labels.basins.len(), 1);
+        assert_eq!(labels.basins[0].size, rows * cols);
+        assert_eq!(labels.basins[0].low, (rows / 2, cols / 2));
+        assert_eq!(labels.basins[0].bbox, (0, 0, rows - 1, cols - 1));
+    }
+
+    #[test]
+    fn test_basin_config() {
+        use basin::{Config, Connectivity};
+
+        let f = "2199943210
+3987894921
+9856789892
+8767896789
+9899965678"
+            .as_bytes();
+        let (data, rows, cols) = parse(f);
+        let sizes = |cfg: &Config| {
+            let mut s: Vec<_> = basin::label(&data, rows, cols, cfg)
+                .basins
+                .iter()
+                .map(|b| b.size)
+                .collect();
+            s.sort_unstable();
+            s
+        };
+        assert_eq!(sizes(&Config::default()), vec![3, 9, 9, 14]);
+        let cfg = Config {
+            wall: Some(8),
+            ..Config::default()
+        };
+        assert_eq!(sizes(&cfg), vec![3, 6, 7, 9]);
+        let cfg = Config {
+            connectivity: Connectivity::Eight,
+            ..Config::default()
+        };
+        assert_eq!(sizes(&cfg), vec![35]);
+        let cfg = Config {
+            wall: None,
+            watershed: true,
+            ..Config::default()
+        };
+        assert_eq!(sizes(&cfg).iter().sum::<usize>(), rows * cols);
+        assert_eq!(sizes(&cfg).len(), 4);
+        let low = basin::low_points(&data, rows, cols, Connectivity::Eight);
+        assert_eq!(low, vec![1, 9, 22, 46]);
+
+        // two valleys with no wall between them
+        let (data, rows, cols) = parse("10 20 30 40 30 20 10\n20,30,40,50,40,30,20".as_bytes());
+        assert_eq!((rows, cols), (2, 7));
+        let labels = basin::label(&data, rows, cols, &cfg);
+        assert_eq!(labels.basins.len(), 2);
+        assert_eq!(labels.basins[0].low, (0, 0));
+        assert_eq!(labels.basins[1].low, (0, 6));
+        let cfg = Config {
+            wall: None,
+            ..Config::default()
+        };
+        assert_eq!(basin::label(&data, rows, cols, &cfg).basins.len(), 1);
+
+        // a flat ridge between two low points splits between them
+        let (data, rows, cols) = parse("15551\n25552".as_bytes());
+        let cfg = Config {
+            wall: None,
+            watershed: true,
+            ..Config::default()
+        };
+        let labels = basin::label(&data, rows, cols, &cfg);
+        let mut out = vec![];
+        labels.write(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "0 0 0 1 1\n0 0 0 1 1\n");
+        assert_eq!(labels.basins[0].low, (0, 0));
+        assert_eq!(labels.basins[1].low, (0, 4));
     }
 }
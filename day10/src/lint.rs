@@ -0,0 +1,437 @@
+use std::{error::Error, fmt};
+
+#[derive(Debug, Clone)]
+pub struct Syntax {
+    pub pairs: Vec<(char, char)>,
+    pub quotes: Vec<char>,
+    pub escape: Option<char>,
+    pub line_comment: Option<String>,
+    pub block_comment: Option<(String, String)>,
+}
+
+impl Default for Syntax {
+    fn default() -> Self {
+        Self {
+            pairs: vec![('(', ')'), ('[', ']'), ('{', '}'), ('<', '>')],
+            quotes: vec![],
+            escape: None,
+            line_comment: None,
+            block_comment: None,
+        }
+    }
+}
+
+fn single(s: &str) -> Result<char, Box<dyn Error>> {
+    let mut c = s.chars();
+    match (c.next(), c.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err(format!("expected a single character, got {:?}", s).into()),
+    }
+}
+
+impl Syntax {
+    // one rule per line:
+    //   pair <open> <close>
+    //   quote <char>
+    //   escape <char>
+    //   line-comment <start>
+    //   block-comment <start> <end>
+    pub fn parse(s: &str) -> Result<Self, Box<dyn Error>> {
+        let mut ret = Self {
+            pairs: vec![],
+            ..Self::default()
+        };
+        for l in s.lines() {
+            let words: Vec<_> = l.split_whitespace().collect();
+            match words[..] {
+                [] => {}
+                ["pair", o, c] => ret.pairs.push((single(o)?, single(c)?)),
+                ["quote", q] => ret.quotes.push(single(q)?),
+                ["escape", e] => ret.escape = Some(single(e)?),
+                ["line-comment", s] => ret.line_comment = Some(s.to_string()),
+                ["block-comment", s, e] => ret.block_comment = Some((s.to_string(), e.to_string())),
+                _ => return Err(format!("bad syntax rule {:?}", l).into()),
+            }
+        }
+        Ok(ret)
+    }
+
+    fn closer(&self, c: char) -> Option<char> {
+        self.pairs.iter().find(|p| p.0 == c).map(|p| p.1)
+    }
+
+    fn is_closer(&self, c: char) -> bool {
+        self.pairs.iter().any(|p| p.1 == c)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pos {
+    pub line: usize,
+    pub col: usize,
+    // characters from the start of the text
+    pub offset: usize,
+}
+
+impl Default for Pos {
+    fn default() -> Self {
+        Self {
+            line: 1,
+            col: 1,
+            offset: 0,
+        }
+    }
+}
+
+impl fmt::Display for Pos {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Diagnostic {
+    Mismatched {
+        found: char,
+        at: Pos,
+        expected: char,
+        opener: Pos,
+    },
+    Unexpected {
+        found: char,
+        at: Pos,
+    },
+    Unclosed {
+        opener: char,
+        at: Pos,
+        expected: char,
+    },
+    UnterminatedString {
+        at: Pos,
+        quote: char,
+    },
+    UnterminatedComment {
+        at: Pos,
+        end: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Suggestion {
+    Replace { at: Pos, with: char },
+    Delete { at: Pos },
+    Append { text: String },
+}
+
+impl Diagnostic {
+    pub fn suggestion(&self) -> Suggestion {
+        match self {
+            &Self::Mismatched { at, expected, .. } => Suggestion::Replace { at, with: expected },
+            &Self::Unexpected { at, .. } => Suggestion::Delete { at },
+            &Self::Unclosed { expected, .. } => Suggestion::Append {
+                text: expected.to_string(),
+            },
+            &Self::UnterminatedString { quote, .. } => Suggestion::Append {
+                text: quote.to_string(),
+            },
+            Self::UnterminatedComment { end, .. } => Suggestion::Append { text: end.clone() },
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Mismatched {
+                found,
+                at,
+                expected,
+                opener,
+            } => write!(
+                f,
+                "{}: expected {:?} to close the opener at {}, found {:?}",
+                at, expected, opener, found
+            ),
+            Self::Unexpected { found, at } => write!(f, "{}: unexpected {:?}", at, found),
+            Self::Unclosed {
+                opener,
+                at,
+                expected,
+            } => write!(f, "{}: {:?} is never closed by {:?}", at, opener, expected),
+            Self::UnterminatedString { at, quote } => {
+                write!(f, "{}: string is never closed by {:?}", at, quote)
+            }
+            Self::UnterminatedComment { at, end } => {
+                write!(f, "{}: comment is never closed by {:?}", at, end)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Code,
+    Str { quote: char, at: Pos, escaped: bool },
+    LineComment,
+    BlockComment { at: Pos },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Open {
+    opener: char,
+    closer: char,
+    at: Pos,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub diagnostics: Vec<Diagnostic>,
+    // closes every open string, comment and delimiter, innermost first
+    pub completion: String,
+}
+
+// everything a checker has seen so far, detached from its syntax
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    stack: Vec<Open>,
+    mode: Mode,
+    pending: Vec<(char, Pos)>,
+    pos: Pos,
+    diagnostics: Vec<Diagnostic>,
+}
+
+// Consumes text one character at a time, so it can be fed arbitrary
+// fragments. Characters that may start a comment marker are held back until
+// the marker is complete or ruled out, and only count once released.
+#[derive(Debug, Clone)]
+pub struct Checker<'a> {
+    syntax: &'a Syntax,
+    stack: Vec<Open>,
+    mode: Mode,
+    pending: Vec<(char, Pos)>,
+    pos: Pos,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Checker<'a> {
+    pub fn new(syntax: &'a Syntax) -> Self {
+        Self {
+            syntax,
+            stack: vec![],
+            mode: Mode::Code,
+            pending: vec![],
+            pos: Pos::default(),
+            diagnostics: vec![],
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    // open delimiters, outermost first
+    pub fn open(&self) -> impl Iterator<Item = (char, Pos)> + '_ {
+        self.stack.iter().map(|o| (o.opener, o.at))
+    }
+
+    // position of the next character to be fed
+    pub fn pos(&self) -> Pos {
+        self.pos
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    #[allow(dead_code)]
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            stack: self.stack.clone(),
+            mode: self.mode,
+            pending: self.pending.clone(),
+            pos: self.pos,
+            diagnostics: self.diagnostics.clone(),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn restore(&mut self, c: Checkpoint) {
+        self.stack = c.stack;
+        self.mode = c.mode;
+        self.pending = c.pending;
+        self.pos = c.pos;
+        self.diagnostics = c.diagnostics;
+    }
+
+    // what finish would report if the input ended here
+    #[allow(dead_code)]
+    pub fn report(&self) -> Report {
+        self.clone().finish()
+    }
+
+    pub fn feed(&mut self, s: &str) {
+        for c in s.chars() {
+            self.pending.push((c, self.pos));
+            self.pos.offset += 1;
+            if c == '\n' {
+                self.pos.line += 1;
+                self.pos.col = 1;
+            } else {
+                self.pos.col += 1;
+            }
+            self.drain(false);
+        }
+    }
+
+    fn markers(&self) -> Vec<&'a str> {
+        let s = self.syntax;
+        match self.mode {
+            Mode::Code => s
+                .line_comment
+                .iter()
+                .map(String::as_str)
+                .chain(s.block_comment.iter().map(|b| b.0.as_str()))
+                .collect(),
+            Mode::BlockComment { .. } => s.block_comment.iter().map(|b| b.1.as_str()).collect(),
+            _ => vec![],
+        }
+    }
+
+    fn drain(&mut self, flush: bool) {
+        while !self.pending.is_empty() {
+            let pending: String = self.pending.iter().map(|p| p.0).collect();
+            let markers = self.markers();
+            if !flush
+                && markers
+                    .iter()
+                    .any(|m| m.len() > pending.len() && m.starts_with(&pending))
+            {
+                return;
+            }
+            let marker = markers
+                .into_iter()
+                .filter(|m| !m.is_empty() && pending.starts_with(m))
+                .max_by_key(|m| m.len());
+            if let Some(m) = marker {
+                let at = self.pending[0].1;
+                self.pending.drain(..m.chars().count());
+                self.mode = match self.mode {
+                    Mode::Code if Some(m) == self.syntax.line_comment.as_deref() => {
+                        Mode::LineComment
+                    }
+                    Mode::Code => Mode::BlockComment { at },
+                    _ => Mode::Code,
+                };
+            } else {
+                let (c, at) = self.pending.remove(0);
+                self.process(c, at);
+            }
+        }
+    }
+
+    fn process(&mut self, c: char, at: Pos) {
+        match &mut self.mode {
+            Mode::Code => {
+                if let Some(closer) = self.syntax.closer(c) {
+                    self.stack.push(Open {
+                        opener: c,
+                        closer,
+                        at,
+                    });
+                } else if self.syntax.is_closer(c) {
+                    // a wrong closer still closes the innermost opener, as
+                    // the suggested replacement would
+                    match self.stack.pop() {
+                        Some(o) if o.closer == c => {}
+                        Some(o) => self.diagnostics.push(Diagnostic::Mismatched {
+                            found: c,
+                            at,
+                            expected: o.closer,
+                            opener: o.at,
+                        }),
+                        None => self
+                            .diagnostics
+                            .push(Diagnostic::Unexpected { found: c, at }),
+                    }
+                } else if self.syntax.quotes.contains(&c) {
+                    self.mode = Mode::Str {
+                        quote: c,
+                        at,
+                        escaped: false,
+                    };
+                }
+            }
+            Mode::Str { quote, escaped, .. } => {
+                if *escaped {
+                    *escaped = false;
+                } else if Some(c) == self.syntax.escape {
+                    *escaped = true;
+                } else if c == *quote {
+                    self.mode = Mode::Code;
+                }
+            }
+            Mode::LineComment => {
+                if c == '\n' {
+                    self.mode = Mode::Code;
+                }
+            }
+            Mode::BlockComment { .. } => {}
+        }
+    }
+
+    pub fn finish(mut self) -> Report {
+        self.drain(true);
+        let mut completion = String::new();
+        match self.mode {
+            Mode::Str { quote, at, .. } => {
+                self.diagnostics
+                    .push(Diagnostic::UnterminatedString { at, quote });
+                completion.push(quote);
+            }
+            Mode::BlockComment { at } => {
+                let end = self.syntax.block_comment.as_ref().unwrap().1.clone();
+                completion.push_str(&end);
+                self.diagnostics
+                    .push(Diagnostic::UnterminatedComment { at, end });
+            }
+            Mode::LineComment => completion.push('\n'),
+            Mode::Code => {}
+        }
+        for o in self.stack.iter().rev() {
+            self.diagnostics.push(Diagnostic::Unclosed {
+                opener: o.opener,
+                at: o.at,
+                expected: o.closer,
+            });
+            completion.push(o.closer);
+        }
+        Report {
+            diagnostics: self.diagnostics,
+            completion,
+        }
+    }
+}
+
+pub fn lint(syntax: &Syntax, text: &str) -> Report {
+    let mut c = Checker::new(syntax);
+    c.feed(text);
+    c.finish()
+}
+
+// applies every suggestion: wrong closers are replaced, stray ones dropped
+// and the completion appended
+pub fn repair(syntax: &Syntax, text: &str) -> String {
+    let report = lint(syntax, text);
+    let mut ret: Vec<Option<char>> = text.chars().map(Some).collect();
+    for d in &report.diagnostics {
+        match d.suggestion() {
+            Suggestion::Replace { at, with } => ret[at.offset] = Some(with),
+            Suggestion::Delete { at } => ret[at.offset] = None,
+            Suggestion::Append { .. } => {}
+        }
+    }
+    let mut ret: String = ret.into_iter().flatten().collect();
+    ret.push_str(&report.completion);
+    ret
+}
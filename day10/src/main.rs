@@ -1,4 +1,6 @@
-use std::io::BufRead;
+use std::io::{BufRead, Read};
+
+mod lint;
 
 #[derive(Clone, Copy)]
 struct ParseError {
@@ -41,6 +43,24 @@ fn parse_expr(l: &str) -> Result<String, ParseError> {
         .collect::<String>())
 }
 
+// parse_expr for input arriving in fragments, stopping at the first error
+#[allow(dead_code)]
+fn parse_chunks<'a>(chunks: impl IntoIterator<Item = &'a str>) -> Result<String, ParseError> {
+    let syntax = lint::Syntax::default();
+    let mut checker = lint::Checker::new(&syntax);
+    for chunk in chunks {
+        checker.feed(chunk);
+        if let Some(d) = checker.diagnostics().first() {
+            return match *d {
+                lint::Diagnostic::Mismatched { found, .. }
+                | lint::Diagnostic::Unexpected { found, .. } => Err(ParseError { found }),
+                _ => unreachable!(),
+            };
+        }
+    }
+    Ok(checker.finish().completion)
+}
+
 fn score_corrupt(c: char) -> i32 {
     match c {
         ')' => 3,
@@ -64,10 +84,56 @@ fn score_incomplete(s: &str) -> i64 {
 }
 
 fn main() {
+    // day10 [lint|repair|stream] [syntax file]
+    let args: Vec<_> = std::env::args().skip(1).collect();
+    if let Some(cmd) = args.first() {
+        let syntax = match args.get(1) {
+            Some(f) => lint::Syntax::parse(&std::fs::read_to_string(f).unwrap()).unwrap(),
+            None => lint::Syntax::default(),
+        };
+        if cmd == "stream" {
+            // report problems as soon as each line arrives
+            let mut checker = lint::Checker::new(&syntax);
+            let mut seen = 0;
+            for l in std::io::stdin().lock().split(b'\n') {
+                let mut l = String::from_utf8(l.unwrap()).unwrap();
+                l.push('\n');
+                checker.feed(&l);
+                for d in &checker.diagnostics()[seen..] {
+                    println!("{}", d);
+                }
+                seen = checker.diagnostics().len();
+                let open: String = checker.open().map(|o| o.0).collect();
+                println!(
+                    "{}: depth {} {}",
+                    checker.pos().line - 1,
+                    checker.depth(),
+                    open
+                );
+            }
+            println!("completion: {:?}", checker.finish().completion);
+            return;
+        }
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text).unwrap();
+        match cmd.as_str() {
+            "lint" => {
+                let report = lint::lint(&syntax, &text);
+                for d in &report.diagnostics {
+                    println!("{}", d);
+                }
+                println!("completion: {:?}", report.completion);
+            }
+            "repair" => print!("{}", lint::repair(&syntax, &text)),
+            _ => panic!("unknown command {}", cmd),
+        }
+        return;
+    }
+
     let inp = std::io::BufReader::new(std::io::stdin());
     let m = inp
         .lines()
-        .flatten()
+        .map_while(Result::ok)
         .map(|s| parse_expr(&s))
         .collect::<Vec<_>>();
     println!(
@@ -94,7 +160,7 @@ mod tests {
         let f = include_bytes!("../test/input.txt");
         let m = &f[..]
             .lines()
-            .flatten()
+            .map_while(Result::ok)
             .map(|s| parse_expr(&s))
             .collect::<Vec<_>>();
         assert_eq!(
@@ -111,4 +177,119 @@ mod tests {
         let n = l.len() / 2;
         assert_eq!(*l.select_nth_unstable(n).1, 288_957);
     }
+
+    #[test]
+    fn test_lint() {
+        use lint::{Diagnostic, Pos};
+
+        // the first error of each line agrees with parse_expr
+        let syntax = lint::Syntax::default();
+        let f = include_bytes!("../test/input.txt");
+        for l in f.lines().map_while(Result::ok) {
+            let report = lint::lint(&syntax, &l);
+            let first = report.diagnostics.iter().find_map(|d| match *d {
+                Diagnostic::Mismatched { found, .. } | Diagnostic::Unexpected { found, .. } => {
+                    Some(found)
+                }
+                _ => None,
+            });
+            match parse_expr(&l) {
+                Ok(s) => assert_eq!((first, report.completion), (None, s)),
+                Err(e) => assert_eq!(first, Some(e.found)),
+            }
+        }
+
+        let text = "{([(<{}[<>[]}>{[]{[(<()>";
+        let report = lint::lint(&syntax, text);
+        assert_eq!(
+            report.diagnostics[0],
+            Diagnostic::Mismatched {
+                found: '}',
+                at: Pos {
+                    line: 1,
+                    col: 13,
+                    offset: 12
+                },
+                expected: ']',
+                opener: Pos {
+                    line: 1,
+                    col: 8,
+                    offset: 7
+                },
+            }
+        );
+        assert_eq!(
+            report.diagnostics[0].to_string(),
+            "1:13: expected ']' to close the opener at 1:8, found '}'"
+        );
+        let repaired = lint::repair(&syntax, text);
+        assert!(lint::lint(&syntax, &repaired).diagnostics.is_empty());
+        assert_eq!(lint::repair(&syntax, "())"), "()");
+    }
+
+    #[test]
+    fn test_lint_syntax() {
+        use lint::Diagnostic;
+
+        let syntax = lint::Syntax::parse(
+            "pair ( )
+pair { }
+quote \"
+escape \\
+line-comment //
+block-comment /* */",
+        )
+        .unwrap();
+        let text = r#"f("(\")", { // }
+  /* ) */ g(1/2) })"#;
+        assert_eq!(lint::lint(&syntax, text).diagnostics, vec![]);
+
+        let report = lint::lint(&syntax, "{ \"}\n(");
+        assert_eq!(report.completion, "\"}");
+        assert!(matches!(
+            report.diagnostics[..],
+            [
+                Diagnostic::UnterminatedString { .. },
+                Diagnostic::Unclosed { .. }
+            ]
+        ));
+        let report = lint::lint(&syntax, "( /* )");
+        assert_eq!(report.completion, "*/)");
+        assert_eq!(lint::repair(&syntax, "(}\n// ("), "()\n// (\n");
+        assert!(lint::Syntax::parse("pair ab )").is_err());
+    }
+
+    #[test]
+    fn test_incremental() {
+        let f = include_bytes!("../test/input.txt");
+        for l in f.lines().map_while(Result::ok) {
+            let expected = parse_expr(&l).map_err(|e| e.found);
+            for size in 1..=5 {
+                let chars: Vec<_> = l.chars().collect();
+                let chunks: Vec<String> = chars.chunks(size).map(|c| c.iter().collect()).collect();
+                let r = parse_chunks(chunks.iter().map(String::as_str));
+                assert_eq!(r.map_err(|e| e.found), expected);
+            }
+        }
+
+        let syntax = lint::Syntax::parse("pair ( )\npair [ ]\nblock-comment (* *)").unwrap();
+        let mut c = lint::Checker::new(&syntax);
+        c.feed("[(");
+        // the '(' may still start a comment
+        assert_eq!(c.depth(), 1);
+        c.feed("x");
+        assert_eq!(c.open().map(|o| o.0).collect::<String>(), "[(");
+        let saved = c.checkpoint();
+        c.feed("]");
+        assert_eq!(c.diagnostics().len(), 1);
+        assert_eq!(c.report().completion, "]");
+        c.restore(saved.clone());
+        assert_eq!(c.checkpoint(), saved);
+        c.feed("(* ] *)");
+        assert_eq!((c.depth(), c.pos().col), (2, 11));
+        c.feed(")]");
+        let report = c.finish();
+        assert_eq!(report.diagnostics, vec![]);
+        assert_eq!(report.completion, "");
+    }
 }
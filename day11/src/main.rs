@@ -1,20 +1,40 @@
+use std::collections::HashMap;
 use std::fmt::{self, Write};
 use std::io::BufRead;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Topology {
+    Bounded,
+    Torus,
+    // rows of hexagons, odd rows shifted half a cell to the right
+    Hex,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Cycle {
+    start: usize,
+    period: usize,
+}
+
+#[derive(Clone)]
 struct Grid {
     data: Vec<i8>,
     rows: usize,
     cols: usize,
+    topology: Topology,
+    // an octopus flashes once its energy exceeds this
+    threshold: i8,
     // state for step
     idx: Vec<usize>,
     flashes: usize,
+    steps: usize,
 }
 
 impl Grid {
     fn parse(l: impl BufRead) -> Self {
         let mut data = vec![];
         let mut rows = 0;
-        for l in l.lines().flatten() {
+        for l in l.lines().map_while(Result::ok) {
             for c in l.chars() {
                 data.push(c.to_digit(10).unwrap() as i8);
             }
@@ -26,18 +46,60 @@ impl Grid {
             data,
             rows,
             cols,
+            topology: Topology::Bounded,
+            threshold: 9,
             idx: vec![],
             flashes: 0,
+            steps: 0,
         }
     }
 
+    fn neighbours(&self, idx: usize) -> Vec<usize> {
+        let r = (idx / self.cols) as i32;
+        let c = (idx % self.cols) as i32;
+        let offsets: &[(i32, i32)] = match (self.topology, r % 2) {
+            (Topology::Hex, 0) => &[(-1, -1), (-1, 0), (0, -1), (0, 1), (1, -1), (1, 0)],
+            (Topology::Hex, _) => &[(-1, 0), (-1, 1), (0, -1), (0, 1), (1, 0), (1, 1)],
+            _ => &[
+                (-1, -1),
+                (-1, 0),
+                (-1, 1),
+                (0, -1),
+                (0, 1),
+                (1, -1),
+                (1, 0),
+                (1, 1),
+            ],
+        };
+        let (rows, cols) = (self.rows as i32, self.cols as i32);
+        let mut ret: Vec<_> = offsets
+            .iter()
+            .filter_map(|&(dr, dc)| {
+                let (r, c) = if self.topology == Topology::Torus {
+                    ((r + dr).rem_euclid(rows), (c + dc).rem_euclid(cols))
+                } else {
+                    (r + dr, c + dc)
+                };
+                if r < 0 || r >= rows || c < 0 || c >= cols {
+                    return None;
+                }
+                Some(r as usize * self.cols + c as usize)
+            })
+            .filter(|&n| n != idx)
+            .collect();
+        // small tori wrap onto the same cell more than once
+        ret.sort_unstable();
+        ret.dedup();
+        ret
+    }
+
     fn step(&mut self) -> usize {
         for (i, d) in self.data.iter_mut().enumerate() {
             if *d < 0 {
                 *d = 0;
             }
             *d += 1;
-            if *d > 9 {
+            if *d > self.threshold {
                 self.idx.push(i);
             }
         }
@@ -46,24 +108,13 @@ impl Grid {
                 continue;
             }
             self.data[idx] = -1;
-            let c = (idx % self.cols) as i32;
-            let r = (idx / self.cols) as i32;
-            for dr in -1..=1 {
-                if (r + dr) < 0 || (r + dr) >= self.rows as i32 {
+            for n in self.neighbours(idx) {
+                if self.data[n] < 0 {
                     continue;
                 }
-                for dc in -1..=1 {
-                    if (c + dc) < 0 || (c + dc) >= self.cols as i32 {
-                        continue;
-                    }
-                    let new_idx = (c + dc) as usize + (r + dr) as usize * self.cols;
-                    if self.data[new_idx] < 0 {
-                        continue;
-                    }
-                    self.data[new_idx] += 1;
-                    if self.data[new_idx] > 9 {
-                        self.idx.push(new_idx);
-                    }
+                self.data[n] += 1;
+                if self.data[n] > self.threshold {
+                    self.idx.push(n);
                 }
             }
         }
@@ -75,8 +126,59 @@ impl Grid {
             }
         }
         self.flashes += flashes;
+        self.steps += 1;
         flashes
     }
+
+    // Steps until every octopus flashes at once, returning that step. The
+    // grid is deterministic, so a repeated state without a synchronized
+    // flash means it never will.
+    fn synchronize(&mut self) -> Result<usize, Cycle> {
+        let mut seen = HashMap::new();
+        loop {
+            if let Some(start) = seen.insert(self.data.clone(), self.steps) {
+                return Err(Cycle {
+                    start,
+                    period: self.steps - start,
+                });
+            }
+            if self.step() == self.rows * self.cols {
+                return Ok(self.steps);
+            }
+        }
+    }
+
+    fn find_cycle(&mut self) -> Cycle {
+        let mut seen = HashMap::new();
+        loop {
+            if let Some(start) = seen.insert(self.data.clone(), self.steps) {
+                return Cycle {
+                    start,
+                    period: self.steps - start,
+                };
+            }
+            self.step();
+        }
+    }
+}
+
+impl Grid {
+    // flashing octopuses glow, the rest darken with lower energy
+    fn palette(&self) -> Vec<animate::Rgb> {
+        let mut p = vec![[255, 240, 160]];
+        p.extend(animate::gradient(
+            [0, 0, 40],
+            [60, 120, 220],
+            self.threshold as usize,
+        ));
+        p
+    }
+
+    fn frame(&self) -> animate::Frame {
+        animate::Frame::from_fn(self.cols, self.rows, |x, y| {
+            self.data[y * self.cols + x] as u8
+        })
+    }
 }
 
 impl fmt::Display for Grid {
@@ -84,7 +186,7 @@ impl fmt::Display for Grid {
         for i in 0..self.rows {
             for j in 0..self.cols {
                 let d = self.data[i * self.cols + j];
-                f.write_char(char::from_digit(d as u32, 10).unwrap())?;
+                f.write_char(char::from_digit(d as u32, 36).unwrap())?;
             }
             if i != self.rows - 1 {
                 f.write_char('\n')?;
@@ -94,25 +196,58 @@ impl fmt::Display for Grid {
     }
 }
 
+// 0 would flash every octopus on every step, and past 35 there is no digit
+// to show the energy with
+fn parse_threshold(s: &str) -> Result<i8, String> {
+    match s.parse() {
+        Ok(t @ 1..=35) => Ok(t),
+        _ => Err(format!("threshold {:?} is not a number from 1 to 35", s)),
+    }
+}
+
 fn main() {
     let mut g = Grid::parse(std::io::BufReader::new(std::io::stdin()));
-    let mut steps = 0;
-    let mut sync_step = 0;
+    // day11 [--torus|--hex] [--threshold=N] [animation flags]
+    let (anim, args) = animate::Output::from_args(std::env::args().skip(1));
+    for a in args {
+        match a.split_once('=') {
+            Some(("--threshold", t)) => g.threshold = parse_threshold(t).unwrap(),
+            None if a == "--torus" => g.topology = Topology::Torus,
+            None if a == "--hex" => g.topology = Topology::Hex,
+            _ => panic!("unknown option {}", a),
+        }
+    }
 
-    while steps < 100 {
-        steps += 1;
-        if g.step() == g.rows * g.cols {
-            sync_step = steps;
+    if let Some(out) = anim {
+        let mut a = animate::Animation::new(g.palette());
+        a.push(g.frame());
+        for _ in 0..out.steps.unwrap_or(100) {
+            g.step();
+            a.push(g.frame());
+        }
+        out.save(&a).unwrap();
+        return;
+    }
+
+    let mut fresh = g.clone();
+    let mut sync_step = None;
+    while g.steps < 100 {
+        if g.step() == g.rows * g.cols && sync_step.is_none() {
+            sync_step = Some(g.steps);
         }
     }
     println!("{}", g.flashes);
-    while sync_step == 0 {
-        steps += 1;
-        if g.step() == g.rows * g.cols {
-            sync_step = steps;
+    match sync_step.ok_or(()).or_else(|_| g.synchronize()) {
+        Ok(s) => println!("{}", s),
+        Err(_) => {
+            // the cycle may have begun during the first 100 steps
+            let c = fresh.find_cycle();
+            println!(
+                "never synchronizes: cycle of period {} from step {}",
+                c.period, c.start
+            );
         }
     }
-    println!("{}", sync_step);
 }
 
 #[cfg(test)]
@@ -173,4 +308,64 @@ mod tests {
         }
         assert_eq!(g.flashes, 1656);
     }
+
+    #[test]
+    fn test_topology() {
+        let mut g = Grid::parse("000\n090\n000".as_bytes());
+        g.topology = Topology::Torus;
+        assert_eq!(g.neighbours(0), vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        g.step();
+        assert_eq!(format!("{}", g), "222\n202\n222");
+
+        g.topology = Topology::Hex;
+        assert_eq!(g.neighbours(4), vec![1, 2, 3, 5, 7, 8]);
+        assert_eq!(g.neighbours(0), vec![1, 3]);
+        assert_eq!(g.neighbours(5), vec![2, 4, 8]);
+
+        let mut g = Grid::parse("11111\n19991\n19191\n19991\n11111".as_bytes());
+        g.threshold = parse_threshold("10").unwrap();
+        g.step();
+        assert_eq!(format!("{}", g), "22222\n2aaa2\n2a2a2\n2aaa2\n22222");
+        g.step();
+        assert_eq!(format!("{}", g), "45654\n50005\n60006\n50005\n45654");
+        for bad in ["0", "-3", "36", "127", "x"] {
+            assert!(parse_threshold(bad).is_err(), "{}", bad);
+        }
+        let mut g = Grid::parse("9".as_bytes());
+        g.threshold = parse_threshold("35").unwrap();
+        for _ in 0..26 {
+            g.step();
+        }
+        assert_eq!(format!("{}", g), "z");
+    }
+
+    #[test]
+    fn test_cycle() {
+        let mut g = Grid::parse("5483143223\n2745854711\n5264556173\n6141336146\n6357385478\n4167524645\n2176841721\n6882881134\n4846848554\n5283751526".as_bytes());
+        assert_eq!(g.synchronize(), Ok(195));
+        // synchronized grids count up and flash together every 10 steps
+        assert_eq!(
+            g.find_cycle(),
+            Cycle {
+                start: 195,
+                period: 10
+            }
+        );
+
+        let mut g = Grid::parse("15".as_bytes());
+        g.topology = Topology::Hex;
+        g.threshold = 3;
+        let cycle = g.clone().find_cycle();
+        assert_eq!(g.synchronize(), Err(cycle));
+    }
+
+    #[test]
+    fn test_frame() {
+        let mut g = Grid::parse("11111\n19991\n19191\n19991\n11111".as_bytes());
+        g.step();
+        let f = g.frame();
+        assert_eq!((f.width, f.height), (5, 5));
+        assert_eq!(&f.pixels[5..10], &[4, 0, 0, 0, 4]);
+        assert_eq!(g.palette().len(), 10);
+    }
 }
@@ -0,0 +1,69 @@
+use std::{error::Error, fmt};
+
+use crate::{policy::Policy, CaveGraph};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Diagnostic {
+    Missing(String),
+    Unreachable(String),
+    // two adjacent caves with no visit limit, which paths can bounce
+    // between forever
+    Endless(String, String),
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Missing(c) => write!(f, "there is no cave {:?}", c),
+            Self::Unreachable(c) => write!(f, "cave {:?} cannot be reached", c),
+            Self::Endless(a, b) => write!(
+                f,
+                "caves {:?} and {:?} are adjacent and unlimited, so there are infinitely many paths",
+                a, b
+            ),
+        }
+    }
+}
+
+impl Error for Diagnostic {}
+
+pub fn diagnose(g: &CaveGraph, p: &Policy) -> Vec<Diagnostic> {
+    let mut ret = vec![];
+    for name in [&p.start, &p.end] {
+        if !g.index.contains_key(name) {
+            ret.push(Diagnostic::Missing(name.clone()));
+        }
+    }
+    let Some(&start) = g.index.get(&p.start) else {
+        return ret;
+    };
+
+    // every cave the start connects to, ignoring visit limits
+    let mut reached = vec![false; g.caves.len()];
+    reached[start] = true;
+    let mut todo = vec![start];
+    while let Some(c) = todo.pop() {
+        for &n in &g.caves[c].next {
+            if !reached[n] && !p.blocked(&g.caves[n]) {
+                reached[n] = true;
+                todo.push(n);
+            }
+        }
+    }
+    for (c, cave) in g.caves.iter().enumerate() {
+        if !reached[c] && !p.blocked(cave) {
+            ret.push(Diagnostic::Unreachable(cave.name.clone()));
+        }
+    }
+    // paths never leave the start or the end again, so neither starts a loop
+    let end = g.index.get(&p.end).copied();
+    let endless =
+        |c: usize| reached[c] && c != start && Some(c) != end && p.limit(&g.caves[c]).is_none();
+    for &(a, b) in &g.edges {
+        if endless(a) && endless(b) {
+            let name = |c: usize| g.caves[c].name.clone();
+            ret.push(Diagnostic::Endless(name(a), name(b)));
+        }
+    }
+    ret
+}
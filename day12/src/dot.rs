@@ -0,0 +1,192 @@
+use std::{error::Error, io::Write, iter::Peekable, vec::IntoIter};
+
+use crate::CaveGraph;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Id(String),
+    // `--` or `->`; caves are joined both ways either way
+    Edge,
+    Punct(char),
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, Box<dyn Error>> {
+    let mut ret = vec![];
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '#' => {
+                chars.find(|&c| c == '\n');
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                chars.find(|&c| c == '\n');
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                loop {
+                    match chars.next() {
+                        Some('/') if prev == '*' => break,
+                        Some(c) => prev = c,
+                        None => return Err("unterminated comment".into()),
+                    }
+                }
+            }
+            '-' if matches!(chars.peek(), Some('-' | '>')) => {
+                chars.next();
+                ret.push(Token::Edge);
+            }
+            '"' => {
+                let mut id = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') if matches!(chars.peek(), Some('"' | '\\')) => {
+                            id.push(chars.next().unwrap())
+                        }
+                        Some(c) => id.push(c),
+                        None => return Err("unterminated string".into()),
+                    }
+                }
+                ret.push(Token::Id(id));
+            }
+            '{' | '}' | '[' | ']' | ';' | ',' | '=' | ':' => ret.push(Token::Punct(c)),
+            c if c.is_alphanumeric() || c == '_' || c == '.' || c == '-' => {
+                let mut id = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_' || c == '.') {
+                        break;
+                    }
+                    id.push(c);
+                    chars.next();
+                }
+                ret.push(Token::Id(id));
+            }
+            _ => return Err(format!("unexpected {:?}", c).into()),
+        }
+    }
+    Ok(ret)
+}
+
+struct Parser {
+    tokens: Peekable<IntoIter<Token>>,
+}
+
+impl Parser {
+    fn eat(&mut self, t: &Token) -> bool {
+        self.tokens.next_if_eq(t).is_some()
+    }
+
+    fn expect(&mut self, t: Token) -> Result<(), Box<dyn Error>> {
+        match self.tokens.next() {
+            Some(n) if n == t => Ok(()),
+            n => Err(format!("expected {:?}, found {:?}", t, n).into()),
+        }
+    }
+
+    fn id(&mut self) -> Result<String, Box<dyn Error>> {
+        match self.tokens.next() {
+            Some(Token::Id(id)) => Ok(id),
+            n => Err(format!("expected a name, found {:?}", n).into()),
+        }
+    }
+
+    // a node id, dropping any port
+    fn node(&mut self) -> Result<String, Box<dyn Error>> {
+        let id = self.id()?;
+        while self.eat(&Token::Punct(':')) {
+            self.id()?;
+        }
+        Ok(id)
+    }
+
+    // attribute lists don't matter to caves
+    fn skip_attrs(&mut self) -> Result<(), Box<dyn Error>> {
+        while self.eat(&Token::Punct('[')) {
+            loop {
+                match self.tokens.next() {
+                    Some(Token::Punct(']')) => break,
+                    Some(_) => {}
+                    None => return Err("unterminated attribute list".into()),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn graph(&mut self, g: &mut CaveGraph) -> Result<(), Box<dyn Error>> {
+        let kw = |k: &str| Token::Id(k.to_string());
+        self.eat(&kw("strict"));
+        if !self.eat(&kw("graph")) && !self.eat(&kw("digraph")) {
+            return Err("expected graph or digraph".into());
+        }
+        if matches!(self.tokens.peek(), Some(Token::Id(_))) {
+            self.id()?;
+        }
+        self.expect(Token::Punct('{'))?;
+        while !self.eat(&Token::Punct('}')) {
+            if self.eat(&Token::Punct(';')) {
+                continue;
+            }
+            if self.eat(&kw("subgraph")) {
+                return Err("subgraphs are not supported".into());
+            }
+            if [kw("graph"), kw("node"), kw("edge")]
+                .iter()
+                .any(|k| self.eat(k))
+            {
+                self.skip_attrs()?;
+                continue;
+            }
+            let mut prev = self.node()?;
+            if self.eat(&Token::Punct('=')) {
+                self.id()?;
+                continue;
+            }
+            g.node(&prev);
+            while self.eat(&Token::Edge) {
+                let next = self.node()?;
+                g.add_edge(&prev, &next);
+                prev = next;
+            }
+            self.skip_attrs()?;
+        }
+        match self.tokens.next() {
+            None => Ok(()),
+            Some(t) => Err(format!("unexpected {:?} after the graph", t).into()),
+        }
+    }
+}
+
+pub fn parse(s: &str) -> Result<CaveGraph, Box<dyn Error>> {
+    let mut p = Parser {
+        tokens: tokenize(s)?.into_iter().peekable(),
+    };
+    let mut g = CaveGraph::new();
+    p.graph(&mut g)?;
+    Ok(g)
+}
+
+// big caves are drawn as filled boxes, small ones as plain ellipses
+pub fn write(g: &CaveGraph, w: &mut impl Write) -> std::io::Result<()> {
+    // DOT escapes nothing in quoted names but the quotes themselves and the
+    // backslashes that would otherwise start an escape
+    let quote = |c: usize| {
+        let name = &g.caves[c].name;
+        format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+    };
+    writeln!(w, "graph caves {{")?;
+    writeln!(w, "    node [shape=ellipse];")?;
+    for (i, c) in g.caves.iter().enumerate() {
+        if c.big {
+            writeln!(w, "    {} [shape=box, style=filled];", quote(i))?;
+        } else {
+            writeln!(w, "    {};", quote(i))?;
+        }
+    }
+    for &(a, b) in &g.edges {
+        writeln!(w, "    {} -- {};", quote(a), quote(b))?;
+    }
+    writeln!(w, "}}")
+}
@@ -1,119 +1,238 @@
-use std::{
-    cell::RefCell,
-    collections::{HashMap, HashSet},
-    io::BufRead,
-    rc::Rc,
-};
+use std::{collections::HashMap, error::Error, io::BufRead};
+
+mod check;
+mod dot;
+mod policy;
+
+use check::Diagnostic;
+use policy::{Paths, Policy, Rules, State};
 
 struct Cave {
     name: String,
     big: bool,
-    next: Vec<Rc<RefCell<Cave>>>,
+    next: Vec<usize>,
 }
 
+// caves live in one arena and refer to each other by index
 struct CaveGraph {
-    caves: HashMap<String, Rc<RefCell<Cave>>>,
+    caves: Vec<Cave>,
+    index: HashMap<String, usize>,
+    edges: Vec<(usize, usize)>,
 }
 
+type Memo = HashMap<(usize, State), u64>;
+
 impl CaveGraph {
     fn new() -> Self {
         Self {
-            caves: HashMap::new(),
+            caves: vec![],
+            index: HashMap::new(),
+            edges: vec![],
         }
     }
 
-    fn parse(f: impl BufRead) -> Self {
+    fn parse(mut f: impl BufRead) -> Self {
+        let mut s = String::new();
+        f.read_to_string(&mut s).unwrap();
+        Self::read(&s).unwrap()
+    }
+
+    // a Graphviz graph, or else an `a-b` edge list
+    fn read(s: &str) -> Result<Self, Box<dyn Error>> {
+        let first = s.split_whitespace().next().unwrap_or("");
+        if ["graph", "digraph", "strict"].contains(&first) {
+            return dot::parse(s);
+        }
         let mut g = Self::new();
-        for l in f.lines().flatten() {
-            let mut l = l.split('-');
-            g.add_edge(l.next().unwrap(), l.next().unwrap());
+        for l in s.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            match l.split_once('-') {
+                Some((a, b)) if !a.is_empty() && !b.is_empty() => g.add_edge(a, b),
+                _ => return Err(format!("bad edge {:?}", l).into()),
+            }
         }
-        g
+        Ok(g)
     }
 
     fn add_edge(&mut self, a: &str, b: &str) {
         let a = self.node(a);
         let b = self.node(b);
-        a.borrow_mut().next.push(b.clone());
-        b.borrow_mut().next.push(a);
-    }
-
-    fn node(&mut self, name: &str) -> Rc<RefCell<Cave>> {
-        self.caves
-            .entry(name.to_string())
-            .or_insert_with(|| {
-                Rc::new(RefCell::new(Cave {
-                    name: name.to_string(),
-                    big: name.chars().next().unwrap().is_uppercase(),
-                    next: vec![],
-                }))
-            })
-            .clone()
-    }
-
-    fn count_paths(&self, start: &str, end: &str) -> i32 {
-        self.dfs(
-            &self.caves.get(start).unwrap().borrow(),
-            end,
-            &mut HashSet::<String>::from_iter([start.to_string()]),
-            true,
-        )
+        self.caves[a].next.push(b);
+        self.caves[b].next.push(a);
+        self.edges.push((a, b));
     }
 
-    fn count_paths_with_extra(&self, start: &str, end: &str) -> i32 {
-        self.dfs(
-            &self.caves.get(start).unwrap().borrow(),
-            end,
-            &mut HashSet::<String>::from_iter([start.to_string()]),
-            false,
-        )
+    fn node(&mut self, name: &str) -> usize {
+        if let Some(&idx) = self.index.get(name) {
+            return idx;
+        }
+        self.caves.push(Cave {
+            name: name.to_string(),
+            big: name.starts_with(char::is_uppercase),
+            next: vec![],
+        });
+        self.index.insert(name.to_string(), self.caves.len() - 1);
+        self.caves.len() - 1
+    }
+
+    fn count_paths(&self, start: &str, end: &str) -> u64 {
+        self.count(&Policy {
+            start: start.to_string(),
+            end: end.to_string(),
+            ..Policy::default()
+        })
+        .unwrap()
+    }
+
+    fn count_paths_with_extra(&self, start: &str, end: &str) -> u64 {
+        self.count(&Policy {
+            start: start.to_string(),
+            end: end.to_string(),
+            ..Policy::with_extra(1)
+        })
+        .unwrap()
+    }
+
+    fn count(&self, p: &Policy) -> Result<u64, Box<dyn Error>> {
+        let rules = self.rules(p)?;
+        Ok(self.dfs(rules.start, rules.initial(), &rules, &mut Memo::new()))
     }
 
-    fn dfs(
-        &self,
-        curr: &Cave,
-        end: &str,
-        small_visited: &mut HashSet<String>,
-        has_extra: bool,
-    ) -> i32 {
-        if curr.name == end {
+    fn paths(&self, p: &Policy) -> Result<Paths<'_>, Box<dyn Error>> {
+        Ok(Paths::new(self, self.rules(p)?))
+    }
+
+    // refuses policies the search would never finish
+    fn rules(&self, p: &Policy) -> Result<Rules, Box<dyn Error>> {
+        let rules = Rules::new(self, p)?;
+        match check::diagnose(self, p)
+            .into_iter()
+            .find(|d| matches!(d, Diagnostic::Endless(..)))
+        {
+            Some(d) => Err(d.into()),
+            None => Ok(rules),
+        }
+    }
+
+    // The paths from `curr` only depend on where we are and how many visits
+    // each limited cave and the extra budget have left, so each such state
+    // is counted once.
+    fn dfs(&self, curr: usize, state: State, rules: &Rules, memo: &mut Memo) -> u64 {
+        if curr == rules.end {
             return 1;
         }
+        let key = (curr, state);
+        if let Some(&n) = memo.get(&key) {
+            return n;
+        }
 
         let mut tot = 0;
-        for c in curr.next.iter().map(|c| c.borrow()) {
-            if c.big {
-                tot += self.dfs(&c, end, small_visited, has_extra);
-            } else if small_visited.get(&c.name).is_none() {
-                small_visited.insert(c.name.clone());
-                tot += self.dfs(&c, end, small_visited, has_extra);
-                small_visited.remove(&c.name);
-            } else if c.name != "start" && !has_extra {
-                tot += self.dfs(&c, end, small_visited, true);
+        for &c in &self.caves[curr].next {
+            if let Some(state) = rules.enter(c, &key.1) {
+                tot += self.dfs(c, state, rules, memo);
             }
         }
+        memo.insert(key, tot);
         tot
     }
 }
 
-impl Drop for CaveGraph {
-    fn drop(&mut self) {
-        for v in self.caves.values_mut() {
-            let mut m = v.borrow_mut();
-            m.next.clear();
-        }
-    }
-}
-
 fn main() {
     let g = CaveGraph::parse(std::io::BufReader::new(std::io::stdin()));
+    // day12 [count|paths|check|dot] [--start=a] [--end=b] [--small=n] [--big=n]
+    //       [--limit=cave:n] [--extra=k] [--forbid=cave]
+    let args: Vec<_> = std::env::args().skip(1).collect();
+    if let Some(cmd) = args.first() {
+        let mut p = Policy::default();
+        for a in &args[1..] {
+            match a.split_once('=') {
+                Some(("--start", c)) => p.start = c.to_string(),
+                Some(("--end", c)) => p.end = c.to_string(),
+                Some(("--small", n)) => p.small = n.parse().unwrap(),
+                Some(("--big", n)) => p.big = Some(n.parse().unwrap()),
+                Some(("--extra", n)) => p.extra = n.parse().unwrap(),
+                Some(("--forbid", c)) => {
+                    p.forbidden.insert(c.to_string());
+                }
+                Some(("--limit", l)) => {
+                    let (c, n) = l.split_once(':').unwrap();
+                    p.limits.insert(c.to_string(), n.parse().unwrap());
+                }
+                _ => panic!("unknown option {}", a),
+            }
+        }
+        match cmd.as_str() {
+            "count" => println!("{}", g.count(&p).unwrap()),
+            "paths" => {
+                for path in g.paths(&p).unwrap() {
+                    println!("{}", path.join(","));
+                }
+            }
+            "check" => {
+                for d in check::diagnose(&g, &p) {
+                    println!("{}", d);
+                }
+            }
+            "dot" => dot::write(&g, &mut std::io::stdout().lock()).unwrap(),
+            _ => panic!("unknown command {}", cmd),
+        }
+        return;
+    }
     println!("{}", g.count_paths("start", "end"));
     println!("{}", g.count_paths_with_extra("start", "end"));
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::CaveGraph;
+    use crate::{check, dot, policy::Policy, CaveGraph, Diagnostic};
+
+    // a hub with `n` small caves hanging off it
+    fn star(n: usize) -> CaveGraph {
+        let mut g = CaveGraph::new();
+        g.add_edge("start", "H");
+        g.add_edge("H", "end");
+        for i in 0..n {
+            g.add_edge("H", &format!("c{}", i));
+        }
+        g
+    }
+
+    #[test]
+    fn test_memoized_scale() {
+        let n = 14;
+        let fact = |k: u64| (1..=k).product::<u64>();
+        let choose = |n: u64, k: u64| fact(n) / fact(k) / fact(n - k);
+        // any ordered choice of the small caves, then the end
+        let once: u64 = (0..=n).map(|k| fact(n) / fact(n - k)).sum();
+        // or one cave twice and k others, in any order
+        let twice: u64 = (0..n).map(|k| n * choose(n - 1, k) * fact(k + 2) / 2).sum();
+        let g = star(n as usize);
+        assert_eq!(g.count_paths("start", "end"), once);
+        assert_eq!(g.count_paths_with_extra("start", "end"), once + twice);
+        assert_eq!(star(2).count_paths_with_extra("start", "end"), 13);
+    }
+
+    #[test]
+    fn test_many_small_caves() {
+        // a line of small caves, each with a big one to bounce off
+        let n = 70;
+        let mut g = CaveGraph::new();
+        g.add_edge("start", "c0");
+        for i in 0..n {
+            g.add_edge(&format!("c{}", i), &format!("B{}", i));
+            let next = if i + 1 < n {
+                format!("c{}", i + 1)
+            } else {
+                "end".to_string()
+            };
+            g.add_edge(&format!("c{}", i), &next);
+        }
+        assert_eq!(g.count_paths("start", "end"), 1);
+        assert_eq!(g.count_paths_with_extra("start", "end"), n + 1);
+        assert_eq!(
+            g.paths(&Policy::with_extra(1)).unwrap().count() as u64,
+            n + 1
+        );
+    }
 
     #[test]
     fn test_count_path() {
@@ -129,6 +248,148 @@ mod tests {
         assert_eq!(g.count_paths_with_extra("start", "end"), 36);
     }
 
+    #[test]
+    fn test_policies() {
+        let g = CaveGraph::parse("start-A\nstart-b\nA-c\nA-b\nb-d\nA-end\nb-end".as_bytes());
+        let check = |p: &Policy| {
+            let n = g.count(p).unwrap();
+            assert_eq!(g.paths(p).unwrap().count() as u64, n);
+            n
+        };
+        let paths: Vec<_> = g.paths(&Policy::default()).unwrap().collect();
+        assert_eq!(paths.len(), 10);
+        assert!(paths.contains(&vec!["start", "A", "b", "A", "c", "A", "end"]));
+        assert!(paths.contains(&vec!["start", "b", "end"]));
+        assert_eq!(check(&Policy::with_extra(1)), 36);
+        assert!(check(&Policy::with_extra(2)) > 36);
+
+        let mut p = Policy::default();
+        p.forbidden.insert("A".to_string());
+        assert_eq!(check(&p), 1);
+        let mut p = Policy::default();
+        p.limits.insert("b".to_string(), 2);
+        let n = check(&p);
+        assert!(n > 10 && n < 36);
+        for path in g.paths(&p).unwrap() {
+            assert!(path.iter().filter(|&&c| c == "b").count() <= 2);
+            assert!(path.iter().filter(|&&c| c == "c").count() <= 1);
+        }
+        let p = Policy {
+            big: Some(1),
+            ..Policy::default()
+        };
+        assert_eq!(check(&p), 4);
+        let p = Policy {
+            start: "c".to_string(),
+            end: "d".to_string(),
+            ..Policy::default()
+        };
+        // "start" is an ordinary small cave here
+        assert_eq!(check(&p), 9);
+        let p = Policy {
+            end: "start".to_string(),
+            ..Policy::default()
+        };
+        assert_eq!(
+            g.paths(&p).unwrap().collect::<Vec<_>>(),
+            vec![vec!["start"]]
+        );
+        let p = Policy {
+            end: "nowhere".to_string(),
+            ..Policy::default()
+        };
+        assert!(g.count(&p).is_err());
+    }
+
+    #[test]
+    fn test_dot() {
+        let g = CaveGraph::parse("start-A\nstart-b\nA-c\nA-b\nb-d\nA-end\nb-end".as_bytes());
+        let mut out = vec![];
+        dot::write(&g, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("    \"A\" [shape=box, style=filled];\n    \"b\";\n"));
+        assert!(out.contains("    \"b\" -- \"end\";\n}\n"));
+        let h = CaveGraph::read(&out).unwrap();
+        assert_eq!(h.edges, g.edges);
+        assert_eq!(h.count_paths_with_extra("start", "end"), 36);
+
+        // names come back whatever characters they hold
+        let mut g = CaveGraph::new();
+        g.add_edge("start", "café \"x\" \\n\u{7}");
+        let mut out = vec![];
+        dot::write(&g, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("\"café \\\"x\\\" \\\\n\u{7}\""));
+        let h = CaveGraph::read(&out).unwrap();
+        assert_eq!(h.caves[1].name, g.caves[1].name);
+
+        let h = CaveGraph::read(
+            r#"strict graph "caves" {
+                // generated
+                rankdir=LR; node [shape=circle]
+                start -- A -- end [color=red]
+                A -> "b" /* both ways */ ; b:n -- end
+                # isolated
+                lonely
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(h.caves.len(), 5);
+        assert_eq!(h.count_paths("start", "end"), 3);
+        for bad in [
+            "graph {",
+            "graph { a -- }",
+            "digraph { subgraph x { a } }",
+            "{}",
+        ] {
+            assert!(CaveGraph::read(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let g = CaveGraph::parse("start-A\nA-B\nB-end\nx-y\nstart-c".as_bytes());
+        let p = Policy::default();
+        let d = check::diagnose(&g, &p);
+        assert_eq!(
+            d,
+            vec![
+                Diagnostic::Unreachable("x".to_string()),
+                Diagnostic::Unreachable("y".to_string()),
+                Diagnostic::Endless("A".to_string(), "B".to_string()),
+            ]
+        );
+        assert!(g.count(&p).is_err());
+        assert!(g.paths(&p).is_err());
+        // limiting either cave breaks the loop
+        let mut p = Policy::default();
+        p.limits.insert("B".to_string(), 2);
+        assert_eq!(check::diagnose(&g, &p).len(), 2);
+        assert_eq!(g.count(&p).unwrap(), 2);
+
+        let p = Policy {
+            start: "begin".to_string(),
+            ..Policy::default()
+        };
+        assert_eq!(
+            check::diagnose(&g, &p),
+            vec![Diagnostic::Missing("begin".to_string())]
+        );
+        let mut p = Policy::default();
+        p.forbidden.insert("B".to_string());
+        assert!(check::diagnose(&g, &p).contains(&Diagnostic::Unreachable("end".to_string())));
+        assert_eq!(g.count(&p).unwrap(), 0);
+
+        // paths stop at the end, so a big end can't bounce
+        let g = CaveGraph::parse("start-A\nA-E".as_bytes());
+        let p = Policy {
+            end: "E".to_string(),
+            ..Policy::default()
+        };
+        assert!(check::diagnose(&g, &p).is_empty());
+        assert_eq!(g.count(&p).unwrap(), 1);
+    }
+
     #[test]
     fn test_parse_count_path() {
         let g = CaveGraph::parse(
@@ -0,0 +1,219 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+};
+
+use crate::{Cave, CaveGraph};
+
+#[derive(Debug, Clone)]
+pub struct Policy {
+    pub start: String,
+    pub end: String,
+    // visits allowed to each small cave, and to each big one if set
+    pub small: u32,
+    pub big: Option<u32>,
+    // per cave overrides of the above
+    pub limits: HashMap<String, u32>,
+    // visits beyond a cave's limit, shared by every cave on the path
+    pub extra: u32,
+    pub forbidden: HashSet<String>,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            start: "start".to_string(),
+            end: "end".to_string(),
+            small: 1,
+            big: None,
+            limits: HashMap::new(),
+            extra: 0,
+            forbidden: HashSet::new(),
+        }
+    }
+}
+
+impl Policy {
+    pub fn with_extra(extra: u32) -> Self {
+        Self {
+            extra,
+            ..Self::default()
+        }
+    }
+
+    // visits allowed to `c` before the extra budget, None if unlimited
+    pub fn limit(&self, c: &Cave) -> Option<u32> {
+        match self.limits.get(&c.name) {
+            Some(&l) => Some(l),
+            None if c.big => self.big,
+            None => Some(self.small),
+        }
+    }
+
+    pub fn blocked(&self, c: &Cave) -> bool {
+        self.forbidden.contains(&c.name) || self.limit(c) == Some(0)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    // the first bit of its count when packed, else its place in the list
+    offset: u32,
+    width: u32,
+    limit: u32,
+}
+
+// The visit counts of every limited cave, packed into a u128 with just
+// enough bits each for its limit plus the extra budget, or one to an entry
+// where that is too many bits.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Visits {
+    Packed(u128),
+    Wide(Vec<u32>),
+}
+
+// the visit counts and how much of the extra budget is used
+pub type State = (Visits, u32);
+
+// A policy resolved against one graph.
+pub struct Rules {
+    pub start: usize,
+    pub end: usize,
+    slots: Vec<Option<Slot>>,
+    // the number of limited caves, if too many to pack
+    wide: Option<usize>,
+    forbidden: Vec<bool>,
+    extra: u32,
+}
+
+impl Rules {
+    pub fn new(g: &CaveGraph, p: &Policy) -> Result<Self, Box<dyn Error>> {
+        let find = |name: &str| {
+            g.index
+                .get(name)
+                .copied()
+                .ok_or_else(|| format!("no cave named {:?}", name))
+        };
+        for name in p.limits.keys().chain(&p.forbidden) {
+            find(name)?;
+        }
+        let mut offset = 0;
+        let mut slots = vec![];
+        let mut forbidden = vec![];
+        for c in &g.caves {
+            forbidden.push(p.blocked(c));
+            slots.push(p.limit(c).map(|limit| {
+                let width = u32::BITS - (limit + p.extra).leading_zeros();
+                offset += width;
+                Slot {
+                    offset: offset - width,
+                    width,
+                    limit,
+                }
+            }));
+        }
+        let mut wide = None;
+        if offset > u128::BITS {
+            let mut n = 0;
+            for s in slots.iter_mut().flatten() {
+                s.offset = n;
+                n += 1;
+            }
+            wide = Some(n as usize);
+        }
+        Ok(Self {
+            start: find(&p.start)?,
+            end: find(&p.end)?,
+            slots,
+            wide,
+            forbidden,
+            extra: p.extra,
+        })
+    }
+
+    // the start is never entered again, so its own visit needs no counting
+    pub fn initial(&self) -> State {
+        match self.wide {
+            Some(n) => (Visits::Wide(vec![0; n]), 0),
+            None => (Visits::Packed(0), 0),
+        }
+    }
+
+    // the state after stepping into `c`, if the policy allows it
+    pub fn enter(&self, c: usize, (visits, used): &State) -> Option<State> {
+        if self.forbidden[c] || c == self.start {
+            return None;
+        }
+        let Some(slot) = self.slots[c] else {
+            return Some((visits.clone(), *used));
+        };
+        let (count, visits) = match visits {
+            Visits::Packed(v) => (
+                ((v >> slot.offset) & ((1 << slot.width) - 1)) as u32,
+                Visits::Packed(v + (1 << slot.offset)),
+            ),
+            Visits::Wide(v) => {
+                let mut v = v.clone();
+                v[slot.offset as usize] += 1;
+                (v[slot.offset as usize] - 1, Visits::Wide(v))
+            }
+        };
+        if count < slot.limit {
+            Some((visits, *used))
+        } else if *used < self.extra {
+            Some((visits, used + 1))
+        } else {
+            None
+        }
+    }
+}
+
+// Depth first enumeration of every path the policy allows.
+pub struct Paths<'a> {
+    g: &'a CaveGraph,
+    rules: Rules,
+    // cave, next neighbour to try and state on arrival, for each step
+    stack: Vec<(usize, usize, State)>,
+}
+
+impl<'a> Paths<'a> {
+    pub fn new(g: &'a CaveGraph, rules: Rules) -> Self {
+        let stack = vec![(rules.start, 0, rules.initial())];
+        Self { g, rules, stack }
+    }
+
+    fn path(&self, end: usize) -> Vec<&'a str> {
+        let g = self.g;
+        self.stack
+            .iter()
+            .map(|s| s.0)
+            .chain([end])
+            .map(|c| g.caves[c].name.as_str())
+            .collect()
+    }
+}
+
+impl<'a> Iterator for Paths<'a> {
+    type Item = Vec<&'a str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.stack.len() == 1 && self.rules.start == self.rules.end {
+            self.stack.clear();
+            return Some(vec![self.g.caves[self.rules.start].name.as_str()]);
+        }
+        while let Some(top) = self.stack.last_mut() {
+            let Some(&c) = self.g.caves[top.0].next.get(top.1) else {
+                self.stack.pop();
+                continue;
+            };
+            top.1 += 1;
+            if let Some(state) = self.rules.enter(c, &top.2) {
+                if c == self.rules.end {
+                    return Some(self.path(c));
+                }
+                self.stack.push((c, 0, state));
+            }
+        }
+        None
+    }
+}
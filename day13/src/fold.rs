@@ -0,0 +1,108 @@
+use std::{error::Error, fmt};
+
+// The side with the larger coordinates is folded over onto the other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Line {
+    X(i32),
+    Y(i32),
+    // x = y + c
+    Diagonal(i32),
+    // x + y = c
+    AntiDiagonal(i32),
+}
+
+impl Line {
+    // x=5, y=7, x=y, x=y+2, x=y-2 or x+y=10
+    pub fn parse(s: &str) -> Result<Self, Box<dyn Error>> {
+        let bad = || format!("bad fold line {:?}", s);
+        let (lhs, rhs) = s.split_once('=').ok_or_else(bad)?;
+        let offset = |s: &str| -> Result<i32, Box<dyn Error>> {
+            match s {
+                "" => Ok(0),
+                s => Ok(s.strip_prefix('+').unwrap_or(s).parse()?),
+            }
+        };
+        Ok(match (lhs, rhs) {
+            ("x+y" | "y+x", c) => Self::AntiDiagonal(c.parse()?),
+            ("x", r) if r.starts_with('y') => Self::Diagonal(offset(&r[1..])?),
+            ("y", r) if r.starts_with('x') => Self::Diagonal(-offset(&r[1..])?),
+            ("x", c) => Self::X(c.parse()?),
+            ("y", c) => Self::Y(c.parse()?),
+            _ => return Err(bad().into()),
+        })
+    }
+
+    fn on(&self, (y, x): (i32, i32)) -> bool {
+        match *self {
+            Self::X(pos) => x == pos,
+            Self::Y(pos) => y == pos,
+            Self::Diagonal(c) => x - y == c,
+            Self::AntiDiagonal(c) => x + y == c,
+        }
+    }
+
+    // where a dot on the folded side lands, None for dots that stay put
+    pub fn reflect(&self, (y, x): (i32, i32)) -> Option<(i32, i32)> {
+        match *self {
+            Self::X(pos) if x > pos => Some((y, 2 * pos - x)),
+            Self::Y(pos) if y > pos => Some((2 * pos - y, x)),
+            Self::Diagonal(c) if x - y > c => Some((x - c, y + c)),
+            Self::AntiDiagonal(c) if x + y > c => Some((c - x, c - y)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::X(pos) => write!(f, "x={}", pos),
+            Self::Y(pos) => write!(f, "y={}", pos),
+            Self::Diagonal(0) => write!(f, "x=y"),
+            Self::Diagonal(c) => write!(f, "x=y{:+}", c),
+            Self::AntiDiagonal(c) => write!(f, "x+y={}", c),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    // dots on the line itself stay where they are
+    OnLine { line: Line, dots: usize },
+    // folding past the middle moves dots beyond the top or left edge
+    Negative { line: Line, dots: usize },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::OnLine { line, dots } => {
+                write!(f, "fold along {}: {} dots on the line", line, dots)
+            }
+            Self::Negative { line, dots } => write!(
+                f,
+                "fold along {}: {} dots land at negative coordinates",
+                line, dots
+            ),
+        }
+    }
+}
+
+pub fn warnings(line: Line, dots: impl Iterator<Item = (i32, i32)> + Clone) -> Vec<Warning> {
+    let mut ret = vec![];
+    let on = dots.clone().filter(|&d| line.on(d)).count();
+    if on > 0 {
+        ret.push(Warning::OnLine { line, dots: on });
+    }
+    let negative = dots
+        .filter_map(|d| line.reflect(d))
+        .filter(|&(y, x)| y < 0 || x < 0)
+        .count();
+    if negative > 0 {
+        ret.push(Warning::Negative {
+            line,
+            dots: negative,
+        });
+    }
+    ret
+}
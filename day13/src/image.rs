@@ -0,0 +1,165 @@
+use std::{
+    collections::BTreeSet,
+    error::Error,
+    io::{self, Write},
+};
+
+type Dots = BTreeSet<(i32, i32)>;
+
+// Every dark pixel marks the dot of the `scale` x `scale` block it lies in,
+// so sheets saved at some scale load back unchanged.
+pub fn load(data: &[u8], scale: usize) -> Result<Dots, Box<dyn Error>> {
+    let (width, pixels) = if data.starts_with(b"\x89PNG") {
+        read_png(data)?
+    } else if data.starts_with(b"P1") || data.starts_with(b"P4") {
+        read_pbm(data)?
+    } else {
+        return Err("expected a PNG or PBM image".into());
+    };
+    let scale = scale.max(1);
+    Ok(pixels
+        .iter()
+        .enumerate()
+        .filter(|&(_, &dark)| dark)
+        .map(|(i, _)| ((i / width / scale) as i32, (i % width / scale) as i32))
+        .collect())
+}
+
+// width and darkness of every pixel, row by row
+fn read_pbm(data: &[u8]) -> Result<(usize, Vec<bool>), Box<dyn Error>> {
+    let mut pos = 2;
+    let mut header = [0usize; 2];
+    for v in &mut header {
+        loop {
+            match data.get(pos) {
+                Some(b'#') => {
+                    while data.get(pos).is_some_and(|&c| c != b'\n') {
+                        pos += 1;
+                    }
+                }
+                Some(c) if c.is_ascii_whitespace() => pos += 1,
+                _ => break,
+            }
+        }
+        let len = data[pos..]
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .count();
+        *v = std::str::from_utf8(&data[pos..pos + len])?.parse()?;
+        pos += len;
+    }
+    let [width, height] = header;
+    if width == 0 {
+        // an empty sheet, with no rows to split the data into
+        return Ok((0, vec![]));
+    }
+    let pixels: Vec<_> = if data[1] == b'1' {
+        data[pos..]
+            .iter()
+            .filter(|c| matches!(c, b'0' | b'1'))
+            .map(|&c| c == b'1')
+            .collect()
+    } else {
+        // a single whitespace byte, then rows padded to whole bytes
+        let row = width.div_ceil(8);
+        data.get(pos + 1..)
+            .unwrap_or_default()
+            .chunks(row)
+            .flat_map(|r| {
+                (0..width).map(move |x| r.get(x / 8).is_some_and(|b| b & 0x80 >> (x % 8) != 0))
+            })
+            .collect()
+    };
+    if pixels.len() < width * height {
+        return Err("truncated PBM image".into());
+    }
+    Ok((width, pixels[..width * height].to_vec()))
+}
+
+fn read_png(data: &[u8]) -> Result<(usize, Vec<bool>), Box<dyn Error>> {
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf)?;
+    let channels = info.color_type.samples();
+    let pixels = buf[..info.buffer_size()]
+        .chunks(channels)
+        .map(|p| {
+            let (colour, alpha) = match channels {
+                1 => (&p[..1], 255),
+                2 => (&p[..1], p[1]),
+                3 => (p, 255),
+                _ => (&p[..3], p[3]),
+            };
+            let luma = colour.iter().map(|&c| u32::from(c)).sum::<u32>() / colour.len() as u32;
+            alpha >= 128 && luma < 128
+        })
+        .collect();
+    Ok((info.width as usize, pixels))
+}
+
+// The image covers the origin and every dot, each dot a `scale` x `scale`
+// black block on white.
+fn raster(dots: &Dots, scale: usize) -> (usize, usize, Vec<bool>) {
+    let (y0, x0) = dots
+        .iter()
+        .fold((0, 0), |m, d| (m.0.min(d.0), m.1.min(d.1)));
+    let (y1, x1) = dots
+        .iter()
+        .fold((0, 0), |m, d| (m.0.max(d.0), m.1.max(d.1)));
+    let (width, height) = ((x1 - x0 + 1) as usize, (y1 - y0 + 1) as usize);
+    let mut pixels = vec![false; width * height * scale * scale];
+    for &(y, x) in dots {
+        let (y, x) = ((y - y0) as usize * scale, (x - x0) as usize * scale);
+        for dy in 0..scale {
+            let row = (y + dy) * width * scale + x;
+            pixels[row..row + scale].fill(true);
+        }
+    }
+    (width * scale, height * scale, pixels)
+}
+
+// rows of 1 bit pixels, 1 for black as PBM has it
+fn pack(width: usize, pixels: &[bool]) -> Vec<u8> {
+    pixels
+        .chunks(width)
+        .flat_map(|r| r.chunks(8))
+        .map(|c| {
+            c.iter()
+                .enumerate()
+                .filter(|&(_, &p)| p)
+                .map(|(i, _)| 0x80 >> i)
+                .sum()
+        })
+        .collect()
+}
+
+// PNG or PBM, by the file's extension
+pub fn save(dots: &Dots, path: &str, scale: usize) -> Result<(), Box<dyn Error>> {
+    let mut w = io::BufWriter::new(std::fs::File::create(path)?);
+    if path.ends_with(".png") {
+        write_png(dots, &mut w, scale)
+    } else {
+        Ok(write_pbm(dots, &mut w, scale)?)
+    }
+}
+
+pub fn write_pbm(dots: &Dots, w: &mut impl Write, scale: usize) -> io::Result<()> {
+    let (width, height, pixels) = raster(dots, scale.max(1));
+    write!(w, "P4\n{} {}\n", width, height)?;
+    w.write_all(&pack(width, &pixels))
+}
+
+pub fn write_png(dots: &Dots, w: &mut impl Write, scale: usize) -> Result<(), Box<dyn Error>> {
+    let (width, height, pixels) = raster(dots, scale.max(1));
+    let mut encoder = png::Encoder::new(w, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::One);
+    let mut writer = encoder.write_header()?;
+    // grayscale runs from black at 0, the opposite of PBM
+    let data: Vec<_> = pack(width, &pixels).iter().map(|b| !b).collect();
+    writer.write_image_data(&data)?;
+    writer.finish()?;
+    Ok(())
+}
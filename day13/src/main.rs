@@ -7,10 +7,28 @@ use std::{
     io::BufRead,
 };
 
+mod fold;
+mod image;
+mod ocr;
+
+use fold::{Line, Warning};
+
+#[derive(Debug, Clone)]
+struct Snapshot {
+    line: Line,
+    // the dots after folding along `line`
+    dots: BTreeSet<(i32, i32)>,
+    warnings: Vec<Warning>,
+}
+
 #[derive(Debug)]
 struct Origami {
     dots: BTreeSet<(i32, i32)>,
-    fold: VecDeque<(char, i32)>,
+    fold: VecDeque<Line>,
+    initial: BTreeSet<(i32, i32)>,
+    history: Vec<Snapshot>,
+    // folds taken back by undo, most recent last
+    undone: Vec<Snapshot>,
 }
 
 impl Origami {
@@ -18,6 +36,9 @@ impl Origami {
         let mut inp = Self {
             dots: BTreeSet::new(),
             fold: VecDeque::new(),
+            initial: BTreeSet::new(),
+            history: vec![],
+            undone: vec![],
         };
 
         let mut lines = f.lines();
@@ -31,41 +52,25 @@ impl Origami {
         }
         for l in &mut lines {
             let l = l?;
-            inp.fold
-                .push_back(scan_fmt!(&l, "fold along {}={}", char, i32)?);
+            let line = l.strip_prefix("fold along ").ok_or("expected a fold")?;
+            inp.fold.push_back(Line::parse(line)?);
         }
+        inp.initial = inp.dots.clone();
 
         Ok(inp)
     }
 
-    fn fold_one(&mut self) -> bool {
-        if let Some((d, pos)) = self.fold.pop_front() {
-            let mut tmp = vec![];
-            match d {
-                'x' => {
-                    self.dots.retain(|&(y, x)| {
-                        if x < pos {
-                            true
-                        } else {
-                            tmp.push((y, 2 * pos - x));
-                            false
-                        }
-                    });
-                }
-                'y' => {
-                    self.dots.retain(|&(y, x)| {
-                        if y < pos {
-                            true
-                        } else {
-                            tmp.push((2 * pos - y, x));
-                            false
-                        }
-                    });
-                }
-                _ => panic!("invalid direction"),
-            }
-            self.dots.extend(tmp.iter());
+    // starts over from a new sheet, keeping the folds still to do
+    fn set_dots(&mut self, dots: BTreeSet<(i32, i32)>) {
+        self.initial = dots.clone();
+        self.dots = dots;
+        self.history.clear();
+        self.undone.clear();
+    }
 
+    fn fold_one(&mut self) -> bool {
+        if let Some(line) = self.fold.pop_front() {
+            self.fold_along(line);
             true
         } else {
             false
@@ -75,16 +80,71 @@ impl Origami {
     fn fold(&mut self) {
         while self.fold_one() {}
     }
+
+    // folds along any line, dropping whatever could have been redone
+    fn fold_along(&mut self, line: Line) -> &[Warning] {
+        let warnings = fold::warnings(line, self.dots.iter().copied());
+        let mut tmp = vec![];
+        self.dots.retain(|&d| match line.reflect(d) {
+            Some(d) => {
+                tmp.push(d);
+                false
+            }
+            None => true,
+        });
+        self.dots.extend(tmp);
+        self.undone.clear();
+        self.history.push(Snapshot {
+            line,
+            dots: self.dots.clone(),
+            warnings,
+        });
+        &self.history.last().unwrap().warnings
+    }
+
+    #[allow(dead_code)]
+    fn undo(&mut self) -> bool {
+        let Some(s) = self.history.pop() else {
+            return false;
+        };
+        // the fold is still to do, so the next fold_one does it again
+        self.fold.push_front(s.line);
+        self.undone.push(s);
+        self.dots = self
+            .history
+            .last()
+            .map_or(&self.initial, |s| &s.dots)
+            .clone();
+        true
+    }
+
+    #[allow(dead_code)]
+    fn redo(&mut self) -> bool {
+        let Some(s) = self.undone.pop() else {
+            return false;
+        };
+        self.fold.pop_front();
+        self.dots = s.dots.clone();
+        self.history.push(s);
+        true
+    }
+
+    // the sheet after each fold so far
+    fn snapshots(&self) -> &[Snapshot] {
+        &self.history
+    }
 }
 
+// dots left of or above the origin shift the drawing rather than break it
 impl Display for Origami {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut prev = (0, 0);
+        let x0 = self.dots.iter().map(|d| d.1).min().unwrap_or(0).min(0);
+        let mut prev = (self.dots.first().map_or(0, |d| d.0).min(0), x0);
         for &(y, x) in &self.dots {
             while y != prev.0 {
                 writeln!(f)?;
                 prev.0 += 1;
-                prev.1 = 0;
+                prev.1 = x0;
             }
             while x != prev.1 {
                 write!(f, " ")?;
@@ -99,10 +159,51 @@ impl Display for Origami {
 
 fn main() {
     let mut o = Origami::parse(std::io::BufReader::new(std::io::stdin())).unwrap();
+    // day13 [steps] [--dots=image] [--out=file.png|file.pbm] [--scale=n]
+    let (mut steps, mut dots, mut out, mut scale) = (false, None, None, 1);
+    for a in std::env::args().skip(1) {
+        match a.split_once('=') {
+            None if a == "steps" => steps = true,
+            Some(("--dots", f)) => dots = Some(f.to_string()),
+            Some(("--out", f)) => out = Some(f.to_string()),
+            Some(("--scale", n)) => scale = n.parse().unwrap(),
+            _ => panic!("unknown option {}", a),
+        }
+    }
+    // after every flag, as the scale may come later
+    if let Some(f) = dots {
+        let data = std::fs::read(f).unwrap();
+        o.set_dots(image::load(&data, scale).unwrap());
+    }
+    if steps {
+        o.fold();
+        for (i, s) in o.snapshots().iter().enumerate() {
+            println!("fold along {}: {} dots", s.line, s.dots.len());
+            for w in &s.warnings {
+                println!("  {}", w);
+            }
+            if let Some((stem, ext)) = out.as_ref().and_then(|f| f.rsplit_once('.')) {
+                let f = format!("{}_{}.{}", stem, i + 1, ext);
+                image::save(&s.dots, &f, scale).unwrap();
+            }
+        }
+        return;
+    }
     o.fold_one();
     println!("{}", o.dots.len());
     o.fold();
     println!("{}", o);
+    for w in o.snapshots().iter().flat_map(|s| &s.warnings) {
+        eprintln!("{}", w);
+    }
+    if let Some(f) = out {
+        image::save(&o.dots, &f, scale).unwrap();
+    }
+    let r = ocr::read(&o.dots);
+    println!("{}", r.text);
+    for u in r.unrecognised {
+        eprintln!("unrecognised glyph {} at {:?}", u.index, u.bbox);
+    }
 }
 
 #[cfg(test)]
@@ -116,4 +217,153 @@ mod tests {
         o.fold_one();
         assert_eq!(o.dots.len(), 17);
     }
+
+    #[test]
+    fn test_history() {
+        let f = include_bytes!("../test/input.txt");
+        let mut o = Origami::parse(&f[..]).unwrap();
+        o.fold();
+        let art = o.to_string();
+        assert_eq!(art, "#####\n#   #\n#   #\n#   #\n#####");
+        assert_eq!(
+            o.snapshots()
+                .iter()
+                .map(|s| s.dots.len())
+                .collect::<Vec<_>>(),
+            vec![17, 16]
+        );
+        assert!(o.undo());
+        assert_eq!(o.dots.len(), 17);
+        assert!(o.undo() && !o.undo());
+        assert_eq!(o.dots, o.initial);
+        assert!(o.redo() && o.redo() && !o.redo());
+        assert_eq!(o.to_string(), art);
+        assert!(o.fold.is_empty());
+
+        // undone folds are folded again
+        assert!(o.undo() && o.undo());
+        assert_eq!(o.fold, [Line::Y(7), Line::X(5)]);
+        assert!(o.fold_one());
+        assert_eq!(o.snapshots()[0].dots.len(), 17);
+        assert!(o.fold_one() && !o.fold_one());
+        assert_eq!(o.to_string(), art);
+        assert!(!o.redo());
+
+        // a fresh fold drops what could have been redone
+        o.undo();
+        assert!(o.fold_along(Line::X(20)).is_empty());
+        assert!(!o.redo());
+        assert_eq!(o.snapshots().len(), 2);
+    }
+
+    #[test]
+    fn test_fold_lines() {
+        for (s, line) in [
+            ("x=5", Line::X(5)),
+            ("y=7", Line::Y(7)),
+            ("x=y", Line::Diagonal(0)),
+            ("x=y+2", Line::Diagonal(2)),
+            ("x=y-2", Line::Diagonal(-2)),
+            ("y=x+2", Line::Diagonal(-2)),
+            ("x+y=10", Line::AntiDiagonal(10)),
+        ] {
+            assert_eq!(Line::parse(s).unwrap(), line);
+            assert_eq!(Line::parse(&line.to_string()).unwrap(), line);
+        }
+        assert!(Line::parse("z=3").is_err());
+
+        let mut o = Origami::parse("3,0\n0,3\n1,1\n\nfold along x=y".as_bytes()).unwrap();
+        o.fold();
+        assert_eq!(o.dots, BTreeSet::from([(1, 1), (3, 0)]));
+        assert_eq!(
+            o.snapshots()[0].warnings,
+            vec![Warning::OnLine {
+                line: Line::Diagonal(0),
+                dots: 1
+            }]
+        );
+        let mut o = Origami::parse("4,0\n0,0\n\nfold along x+y=2".as_bytes()).unwrap();
+        o.fold();
+        assert_eq!(o.dots, BTreeSet::from([(-2, 2), (0, 0)]));
+
+        // folding past the middle
+        let mut o = Origami::parse("0,0\n5,1\n\nfold along x=1".as_bytes()).unwrap();
+        o.fold();
+        assert_eq!(
+            o.snapshots()[0].warnings,
+            vec![Warning::Negative {
+                line: Line::X(1),
+                dots: 1
+            }]
+        );
+        assert_eq!(o.to_string(), "   #\n#");
+    }
+
+    #[test]
+    fn test_images() {
+        let f = include_bytes!("../test/input.txt");
+        let mut o = Origami::parse(&f[..]).unwrap();
+        for scale in [1, 3] {
+            let mut pbm = vec![];
+            image::write_pbm(&o.dots, &mut pbm, scale).unwrap();
+            assert_eq!(image::load(&pbm, scale).unwrap(), o.dots);
+            let mut png = vec![];
+            image::write_png(&o.dots, &mut png, scale).unwrap();
+            assert_eq!(image::load(&png, scale).unwrap(), o.dots);
+        }
+
+        let mut pbm = vec![];
+        image::write_pbm(&BTreeSet::from([(0, 1), (1, 9)]), &mut pbm, 1).unwrap();
+        assert_eq!(pbm, b"P4\n10 2\n\x40\x00\x00\x40");
+        let ascii = "P1\n# a comment\n3 2\n0 1 0\n1 0 1\n";
+        let dots = image::load(ascii.as_bytes(), 1).unwrap();
+        assert_eq!(dots, BTreeSet::from([(0, 1), (1, 0), (1, 2)]));
+        assert!(image::load(b"P1\n3 2\n0 1", 1).is_err());
+        assert!(image::load(b"3,4", 1).is_err());
+        assert!(image::load(b"P4\n0 2\n", 1).unwrap().is_empty());
+
+        // sheets past the origin are shifted into the picture
+        o.set_dots(BTreeSet::from([(-1, -2), (0, 0)]));
+        let mut png = vec![];
+        image::write_png(&o.dots, &mut png, 2).unwrap();
+        assert_eq!(
+            image::load(&png, 2).unwrap(),
+            BTreeSet::from([(0, 0), (1, 2)])
+        );
+        assert!(o.fold_one() && o.snapshots().len() == 1);
+    }
+
+    #[test]
+    fn test_ocr() {
+        let text = "ABCEFGHIJKLOPRSUZ";
+        let r = ocr::read(&ocr::render(text).unwrap());
+        assert_eq!(r.text, text);
+        assert!(r.unrecognised.is_empty());
+        assert_eq!(ocr::render("Q"), None);
+
+        // folded sheets needn't start at the origin, and may leave gaps
+        let dots = ocr::render("HI  L").unwrap();
+        let dots = dots.iter().map(|&(y, x)| (y + 3, x + 7)).collect();
+        assert_eq!(ocr::read(&dots).text, "HI  L");
+        // a blank first column doesn't throw the cells out
+        for x in [0, 3, 12] {
+            let dots = ocr::render("IJ").unwrap();
+            let dots = dots.iter().map(|&(y, x0)| (y, x0 + x)).collect();
+            assert_eq!(ocr::read(&dots).text, "IJ");
+        }
+
+        // the example folds into a square
+        let f = include_bytes!("../test/input.txt");
+        let mut o = Origami::parse(&f[..]).unwrap();
+        o.fold();
+        let r = ocr::read(&o.dots);
+        assert_eq!(r.text, "?");
+        assert_eq!(
+            r.unrecognised,
+            vec![ocr::Unrecognised {
+                index: 0,
+                bbox: (0, 0, 4, 4)
+            }]
+        );
+    }
 }
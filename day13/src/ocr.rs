@@ -0,0 +1,128 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet},
+};
+
+pub const WIDTH: i32 = 4;
+pub const HEIGHT: i32 = 6;
+// glyphs are separated by one blank column
+const PITCH: i32 = WIDTH + 1;
+
+const FONT: [(char, [&str; HEIGHT as usize]); 18] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+    (' ', ["....", "....", "....", "....", "....", "...."]),
+];
+
+// one bit per cell of a glyph, row by row
+fn bits(rows: &[&str]) -> u32 {
+    rows.iter()
+        .flat_map(|r| r.chars())
+        .enumerate()
+        .filter(|&(_, c)| c == '#')
+        .map(|(i, _)| 1 << i)
+        .sum()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unrecognised {
+    // position of the glyph in the text
+    pub index: usize,
+    // (min x, min y, max x, max y) of its dots on the sheet
+    pub bbox: (i32, i32, i32, i32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reading {
+    // '?' in place of every unrecognised glyph
+    pub text: String,
+    pub unrecognised: Vec<Unrecognised>,
+}
+
+// Reads dots stored as (y, x) as a line of block letters, starting at the
+// top dot and at whichever column up to the leftmost dot recognises the
+// most glyphs, as letters like I start with a blank one. Dots in the gaps
+// between glyphs or below the line spoil the glyph they are next to.
+pub fn read(dots: &BTreeSet<(i32, i32)>) -> Reading {
+    let (Some(y0), Some(x0)) = (
+        dots.iter().map(|d| d.0).min(),
+        dots.iter().map(|d| d.1).min(),
+    ) else {
+        return Reading {
+            text: String::new(),
+            unrecognised: vec![],
+        };
+    };
+    (0..PITCH)
+        .map(|k| read_at(dots, y0, x0 - k))
+        .min_by_key(|r| {
+            let bad = r.unrecognised.len();
+            (Reverse(r.text.chars().count() - bad), bad)
+        })
+        .unwrap()
+}
+
+// the glyphs in cells from column x0
+fn read_at(dots: &BTreeSet<(i32, i32)>, y0: i32, x0: i32) -> Reading {
+    let len = (dots.iter().map(|d| d.1).max().unwrap() - x0) / PITCH + 1;
+
+    // bits, whether stray dots were seen and bounding box of each glyph
+    let mut glyphs = BTreeMap::new();
+    for &(y, x) in dots {
+        let (i, col, row) = ((x - x0) / PITCH, (x - x0) % PITCH, y - y0);
+        let g = glyphs.entry(i).or_insert((0u32, false, (x, y, x, y)));
+        if col < WIDTH && row < HEIGHT {
+            g.0 |= 1 << (row * WIDTH + col);
+        } else {
+            g.1 = true;
+        }
+        g.2 = (g.2 .0.min(x), g.2 .1.min(y), g.2 .2.max(x), g.2 .3.max(y));
+    }
+
+    let mut text = String::new();
+    let mut unrecognised = vec![];
+    for i in 0..len {
+        let (g, stray, bbox) = glyphs.get(&i).copied().unwrap_or((0, false, (0, 0, 0, 0)));
+        match FONT.iter().find(|f| !stray && bits(&f.1) == g) {
+            Some(f) => text.push(f.0),
+            None => {
+                text.push('?');
+                unrecognised.push(Unrecognised {
+                    index: i as usize,
+                    bbox,
+                });
+            }
+        }
+    }
+    Reading { text, unrecognised }
+}
+
+// the dots spelling `text`, None if it has letters outside the font
+#[allow(dead_code)]
+pub fn render(text: &str) -> Option<BTreeSet<(i32, i32)>> {
+    let mut dots = BTreeSet::new();
+    for (i, c) in text.chars().enumerate() {
+        let rows = FONT.iter().find(|f| f.0 == c)?.1;
+        for (y, r) in rows.iter().enumerate() {
+            for (x, _) in r.chars().enumerate().filter(|&(_, c)| c == '#') {
+                dots.insert((y as i32, i as i32 * PITCH + x as i32));
+            }
+        }
+    }
+    Some(dots)
+}
@@ -0,0 +1,249 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt,
+};
+
+// A rule `ABC -> XY` inserts XY between the last two characters of its
+// pattern, B and C, when A comes just before them. Patterns are matched
+// against the polymer as it was before the step, so insertions never feed
+// each other. Where rules of different lengths match at a gap the longest
+// wins, and of rules with the same pattern the last one given.
+#[derive(Debug, Default)]
+pub struct Grammar {
+    // in the order given, for the checker
+    list: Vec<(Vec<char>, Vec<char>)>,
+    rules: HashMap<Vec<char>, Vec<char>>,
+    // the endings of patterns, shorter than the patterns themselves
+    tails: HashSet<Vec<char>>,
+    longest: usize,
+    // characters a window keeps left of its anchor
+    reach: usize,
+}
+
+// windows wider than this make too many to count
+const MAX_REACH: usize = 64;
+
+// The counts keep, around each character, enough of the polymer to tell
+// what it grows into and the same again for the characters it grows into.
+// Left of the anchor that is twice the context of the longest rule when
+// every gap there gets an insertion, more when some do not. Windows keep
+// less where the characters before could not be told, which only matters
+// once they decide what the anchor grows into.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Window {
+    chars: Vec<char>,
+    anchor: usize,
+    // whether chars starts the polymer, when the rules care
+    start: bool,
+}
+
+impl Window {
+    pub fn char(&self) -> char {
+        self.chars[self.anchor]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Finding {
+    // the same pattern twice, the last insertion wins
+    Conflict {
+        pattern: String,
+        inserts: (String, String),
+    },
+    // a longer rule taking over a gap from a shorter one
+    Shadowed {
+        rule: String,
+        by: String,
+    },
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Conflict { pattern, inserts } => write!(
+                f,
+                "conflict: {} -> {} and {} -> {}",
+                pattern, inserts.0, pattern, inserts.1
+            ),
+            Self::Shadowed { rule, by } => write!(f, "note: {} overrides {} after it", by, rule),
+        }
+    }
+}
+
+impl Grammar {
+    pub fn add(&mut self, line: &str) -> Result<(), Box<dyn Error>> {
+        let (pattern, insert) = scan_fmt!(line, "{} -> {}", String, String)?;
+        let (pattern, insert): (Vec<_>, Vec<_>) =
+            (pattern.chars().collect(), insert.chars().collect());
+        if pattern.len() < 2 {
+            return Err(format!("rule {:?} needs at least two characters to match", line).into());
+        }
+        for i in 1..pattern.len() - 1 {
+            self.tails.insert(pattern[i..].to_vec());
+        }
+        self.longest = self.longest.max(pattern.len());
+        self.rules.insert(pattern.clone(), insert.clone());
+        self.list.push((pattern, insert));
+        Ok(())
+    }
+
+    pub fn check(&self) -> Vec<Finding> {
+        let s = |c: &[char]| c.iter().collect::<String>();
+        let mut ret = vec![];
+        let mut seen = HashMap::new();
+        for (p, b) in &self.list {
+            match seen.insert(p, b) {
+                Some(a) if a != b => ret.push(Finding::Conflict {
+                    pattern: s(p),
+                    inserts: (s(a), s(b)),
+                }),
+                _ => {}
+            }
+        }
+        let mut shadowed: Vec<_> = self
+            .rules
+            .iter()
+            .flat_map(|(p, a)| {
+                self.rules
+                    .iter()
+                    .filter(move |(q, b)| q.len() > p.len() && q.ends_with(p) && a != *b)
+                    .map(move |(q, _)| (s(q), s(p)))
+            })
+            .collect();
+        shadowed.sort();
+        ret.extend(
+            shadowed
+                .into_iter()
+                .map(|(by, rule)| Finding::Shadowed { rule, by }),
+        );
+        ret
+    }
+
+    // What goes into the gap between the last two characters of `context`,
+    // None if a longer rule might match had it started further left.
+    fn insertion(&self, context: &[char], whole: bool) -> Option<&[char]> {
+        if !whole && self.tails.contains(context) {
+            return None;
+        }
+        let longest = self.longest.min(context.len());
+        Some(
+            (2..=longest)
+                .rev()
+                .find_map(|m| self.rules.get(&context[context.len() - m..]))
+                .map_or(&[], |v| &v[..]),
+        )
+    }
+
+    // one step of the polymer itself
+    #[allow(dead_code)]
+    pub fn apply(&self, s: &[char]) -> Vec<char> {
+        let mut ret = vec![];
+        for i in 0..s.len() {
+            ret.push(s[i]);
+            if i + 1 < s.len() {
+                ret.extend(self.insertion(&s[..i + 2], true).unwrap());
+            }
+        }
+        ret
+    }
+
+    // one window around every character of `s`
+    pub fn windows(&self, s: &[char]) -> Vec<Window> {
+        let reach = self.reach;
+        (0..s.len())
+            .map(|i| {
+                let lo = i.saturating_sub(reach);
+                Window {
+                    chars: s[lo..(i + 2).min(s.len())].to_vec(),
+                    anchor: i - lo,
+                    start: reach > 0 && lo == 0,
+                }
+            })
+            .collect()
+    }
+
+    // The windows around the characters the anchor of `w` grows into, in
+    // order. None if the window is too short to tell them.
+    fn grow(&self, w: &Window) -> Option<Vec<Window>> {
+        let (c, a, reach) = (&w.chars, w.anchor, self.reach);
+        let segment = |j: usize| -> Option<Vec<char>> {
+            let mut seg = vec![c[j]];
+            if j + 1 < c.len() {
+                seg.extend(self.insertion(&c[..j + 2], w.start)?);
+            }
+            Some(seg)
+        };
+
+        let own = segment(a)?;
+        // the new characters before `own`, backwards, as far as they can be
+        // told
+        let mut left = vec![];
+        let mut j = a;
+        while left.len() < reach && j > 0 {
+            let Some(seg) = segment(j - 1) else {
+                break;
+            };
+            j -= 1;
+            left.extend(seg.into_iter().rev());
+        }
+        left.reverse();
+
+        let before = left.len();
+        let mut line = left;
+        line.extend(&own);
+        line.extend(c.get(a + 1));
+        Some(
+            (0..own.len())
+                .map(|q| {
+                    let p = before + q;
+                    let lo = p.saturating_sub(reach);
+                    Window {
+                        chars: line[lo..(p + 2).min(line.len())].to_vec(),
+                        anchor: p - lo,
+                        start: w.start && j == 0 && lo == 0,
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    // Every window the template leads to, with the windows each grows into,
+    // keeping the least context around each character that tells them all.
+    pub fn fit(
+        &mut self,
+        template: &[char],
+    ) -> Result<HashMap<Window, Vec<Window>>, Box<dyn Error>> {
+        let least = 2 * self.longest.max(2) - 4;
+        let mut stuck = vec![];
+        let tries = std::iter::successors(Some(least), |r| Some(2 * r + 1));
+        for reach in tries.take_while(|&r| r <= least.max(MAX_REACH)) {
+            self.reach = reach;
+            match self.transitions(&self.windows(template)) {
+                Ok(next) => return Ok(next),
+                Err(w) => stuck = w,
+            }
+        }
+        Err(format!(
+            "cannot count what grows from {:?}: the rules need more context than {} characters",
+            stuck.iter().collect::<String>(),
+            MAX_REACH
+        )
+        .into())
+    }
+
+    // the characters of the first window found to need more context
+    fn transitions(&self, from: &[Window]) -> Result<HashMap<Window, Vec<Window>>, Vec<char>> {
+        let mut ret = HashMap::new();
+        let mut todo = from.to_vec();
+        while let Some(w) = todo.pop() {
+            if ret.contains_key(&w) {
+                continue;
+            }
+            let next = self.grow(&w).ok_or_else(|| w.chars.clone())?;
+            todo.extend(next.iter().filter(|n| !ret.contains_key(*n)).cloned());
+            ret.insert(w, next);
+        }
+        Ok(ret)
+    }
+}
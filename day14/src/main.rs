@@ -1,105 +1,345 @@
 use std::{collections::HashMap, error::Error, io::BufRead};
 
+use num::{BigUint, ToPrimitive, Zero};
+
 #[macro_use]
 extern crate scan_fmt;
 
+mod grammar;
+mod matrix;
+mod query;
+
+use grammar::{Grammar, Window};
+
 struct Polymer {
-    template: HashMap<(char, char), usize>,
-    rules: HashMap<(char, char), char>,
+    initial: Vec<char>,
+    // how many times each window occurs
+    windows: HashMap<Window, BigUint>,
+    grammar: Grammar,
+    // every window the template leads to, with the windows it grows into
+    next: HashMap<Window, Vec<Window>>,
+    // keep counts modulo this, which lets step_n skip ahead
+    modulus: Option<BigUint>,
 }
 
 impl Polymer {
     fn parse(f: impl BufRead) -> Result<Self, Box<dyn Error>> {
         let mut inp = Self {
-            template: HashMap::new(),
-            rules: HashMap::new(),
+            initial: vec![],
+            windows: HashMap::new(),
+            grammar: Grammar::default(),
+            next: HashMap::new(),
+            modulus: None,
         };
 
         let mut lines = f.lines();
         if let Some(l) = lines.next() {
-            let last = l?.chars().fold('\0', |prev, curr| {
-                *inp.template.entry((prev, curr)).or_insert(0) += 1;
-                curr
-            });
-            *inp.template.entry((last, '\0')).or_insert(0) += 1;
+            inp.initial = l?.chars().collect();
         }
         lines.next();
         for l in lines {
-            let (a, b, c) = scan_fmt!(&l?, "{/[A-Z]/}{} -> {}", char, char, char)?;
-            inp.rules.insert((a, b), c);
+            inp.grammar.add(&l?)?;
+        }
+        inp.next = inp.grammar.fit(&inp.initial)?;
+        for w in inp.grammar.windows(&inp.initial) {
+            *inp.windows.entry(w).or_default() += 1u32;
         }
 
         Ok(inp)
     }
 
+    fn set_modulus(&mut self, m: u64) -> Result<(), Box<dyn Error>> {
+        if m == 0 {
+            return Err("the modulus must be at least 1".into());
+        }
+        let m = BigUint::from(m);
+        for v in self.windows.values_mut() {
+            *v %= &m;
+        }
+        self.modulus = Some(m);
+        Ok(())
+    }
+
+    fn reduce(&self, v: BigUint) -> BigUint {
+        match &self.modulus {
+            Some(m) => v % m,
+            None => v,
+        }
+    }
+
     fn step(&mut self) {
-        let old = std::mem::take(&mut self.template);
-        for (k, v) in old {
-            if let Some(&replace) = self.rules.get(&k) {
-                *self.template.entry((k.0, replace)).or_insert(0) += v;
-                *self.template.entry((replace, k.1)).or_insert(0) += v;
-            } else {
-                *self.template.entry(k).or_insert(0) += v;
+        let old = std::mem::take(&mut self.windows);
+        for (w, v) in old {
+            for n in &self.next[&w] {
+                *self.windows.entry(n.clone()).or_default() += &v;
+            }
+        }
+        if self.modulus.is_some() {
+            let windows = std::mem::take(&mut self.windows);
+            self.windows = windows
+                .into_iter()
+                .map(|(k, v)| (k, self.reduce(v)))
+                .collect();
+        }
+    }
+
+    // Exact counts take every step in turn. Counts modulo a number fit a u64,
+    // so the steps become one power of the matrix taking window counts to
+    // those a step later.
+    fn step_n(&mut self, n: u64) {
+        let Some(m) = self.modulus.as_ref().and_then(|m| m.to_u64()) else {
+            for _ in 0..n {
+                self.step();
+            }
+            return;
+        };
+
+        let index: HashMap<_, _> = self.next.keys().zip(0..).collect();
+        let mut t = matrix::Matrix::zero(index.len(), m);
+        for (w, &i) in &index {
+            for n in &self.next[*w] {
+                t.add(i, index[n], 1);
             }
         }
+        let mut v = vec![0; index.len()];
+        for (w, c) in &self.windows {
+            v[index[w]] = c.to_u64().unwrap();
+        }
+        let v = t.apply_pow(&v, n);
+        self.windows = index
+            .into_iter()
+            .filter(|&(_, i)| v[i] != 0)
+            .map(|(w, i)| (w.clone(), BigUint::from(v[i])))
+            .collect();
     }
 
-    #[allow(dead_code)]
-    fn len(&self) -> usize {
-        self.template.iter().map(|(_, &s)| s).sum::<usize>() - 1
+    fn len(&self) -> BigUint {
+        self.reduce(self.windows.values().sum())
     }
 
-    fn histogram(&self) -> Vec<(char, usize)> {
-        let mut cnt = HashMap::new();
-        self.template.iter().for_each(|(&(a, b), &v)| {
-            *cnt.entry(a).or_insert(0) += v;
-            *cnt.entry(b).or_insert(0) += v;
-        });
-        cnt.remove(&'\0');
+    // the polymer grown from the template over `steps` steps, whatever
+    // steps have been taken so far
+    fn expansion(&self, steps: usize) -> query::Expansion<'_> {
+        let start = self.grammar.windows(&self.initial);
+        query::Expansion::new(&start, &self.next, steps)
+    }
 
-        let mut cnt: Vec<_> = cnt.into_iter().map(|(c, cnt)| (c, cnt / 2)).collect();
-        cnt.sort_unstable_by_key(|&(_, c)| -(c as i64));
+    // Each window counts the character at its anchor. Most common first,
+    // which means little once counts are taken modulo something.
+    fn histogram(&self) -> Vec<(char, BigUint)> {
+        let mut cnt: HashMap<char, BigUint> = HashMap::new();
+        for (w, v) in &self.windows {
+            *cnt.entry(w.char()).or_default() += v;
+        }
+
+        let mut cnt: Vec<_> = cnt
+            .into_iter()
+            .map(|(c, n)| (c, self.reduce(n)))
+            .filter(|(_, n)| !n.is_zero() || self.modulus.is_some())
+            .collect();
+        cnt.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
         cnt
     }
 }
 
 fn main() {
     let mut p = Polymer::parse(std::io::BufReader::new(std::io::stdin())).unwrap();
-    for _ in 0..10 {
-        p.step();
+    // day14 [len <steps> | at <steps> <k> | slice <steps> <from> <to>]
+    //       [count <steps> [modulus] | check]
+    let args: Vec<_> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("check") {
+        for f in p.grammar.check() {
+            println!("{}", f);
+        }
+        return;
     }
-    let h = p.histogram();
-    println!("{}", h[0].1 - h[h.len() - 1].1);
-    for _ in 0..30 {
-        p.step();
+    if args.first().map(String::as_str) == Some("count") {
+        if let Some(m) = args.get(2) {
+            p.set_modulus(m.parse().unwrap()).unwrap();
+        }
+        p.step_n(args[1].parse().unwrap());
+        println!("length {}", p.len());
+        for (c, n) in p.histogram() {
+            println!("{} {}", c, n);
+        }
+        return;
+    }
+    if let Some(cmd) = args.first() {
+        let n: Vec<u64> = args[1..].iter().map(|a| a.parse().unwrap()).collect();
+        let mut e = p.expansion(n[0] as usize);
+        match (cmd.as_str(), &n[1..]) {
+            ("len", []) => println!("{}", e.len()),
+            ("at", &[k]) => println!("{}", e.char_at(k).unwrap_or(' ')),
+            ("slice", &[from, to]) => println!("{}", e.slice(from..to)),
+            _ => panic!("bad arguments {:?}", args),
+        }
+        return;
     }
+    p.step_n(10);
+    let h = p.histogram();
+    println!("{}", &h[0].1 - &h[h.len() - 1].1);
+    p.step_n(30);
     let h = p.histogram();
-    println!("{}", h[0].1 - h[h.len() - 1].1);
+    println!("{}", &h[0].1 - &h[h.len() - 1].1);
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use num::One;
 
     #[test]
     fn test_polymer() {
         let f = include_bytes!("../test/input.txt");
         let mut p = Polymer::parse(&f[..]).unwrap();
-        assert_eq!(p.len(), 4);
+        assert_eq!(p.len(), 4u32.into());
         p.step();
-        assert_eq!(p.len(), 7);
+        assert_eq!(p.len(), 7u32.into());
         for _ in 0..9 {
             p.step();
         }
-        assert_eq!(p.len(), 3073);
+        assert_eq!(p.len(), 3073u32.into());
         let h = p.histogram();
-        assert_eq!(h[0].1, 1749);
-        assert_eq!(h[h.len() - 1].1, 161);
+        assert_eq!(h[0].1, 1749u32.into());
+        assert_eq!(h[h.len() - 1].1, 161u32.into());
         for _ in 0..30 {
             p.step();
         }
         let h = p.histogram();
-        assert_eq!(h[0].1, 2_192_039_569_602);
-        assert_eq!(h[h.len() - 1].1, 3_849_876_073);
+        assert_eq!(h[0].1, 2_192_039_569_602u64.into());
+        assert_eq!(h[h.len() - 1].1, 3_849_876_073u64.into());
+    }
+
+    #[test]
+    fn test_big_counts() {
+        let f = include_bytes!("../test/input.txt");
+        let mut p = Polymer::parse(&f[..]).unwrap();
+        p.step_n(100);
+        // three pairs doubling every step
+        assert_eq!(
+            p.len(),
+            BigUint::from(3u32) * (BigUint::one() << 100) + 1u32
+        );
+        let total: BigUint = p.histogram().into_iter().map(|(_, n)| n).sum();
+        assert_eq!(total, p.len());
+
+        // the matrix agrees with stepping, modulo a prime
+        let m = 1_000_000_007;
+        let mut q = Polymer::parse(&f[..]).unwrap();
+        q.set_modulus(m).unwrap();
+        q.step_n(100);
+        let mut h = p.histogram();
+        for (_, n) in &mut h {
+            *n %= m;
+        }
+        h.sort();
+        let mut hq = q.histogram();
+        hq.sort();
+        assert_eq!(hq, h);
+        assert_eq!(q.len(), p.len() % m);
+
+        assert!(q.set_modulus(0).is_err());
+
+        // and skips ahead in no time
+        let mut q = Polymer::parse(&f[..]).unwrap();
+        q.set_modulus(m).unwrap();
+        q.step_n(1_000_000_000_000_000);
+        let mut r = Polymer::parse(&f[..]).unwrap();
+        r.set_modulus(m).unwrap();
+        r.step_n(999_999_999_999_999);
+        r.step();
+        assert_eq!(q.histogram(), r.histogram());
+    }
+
+    // the polymer itself, for short runs
+    fn grow(p: &Polymer, steps: usize) -> String {
+        let mut s = p.initial.clone();
+        for _ in 0..steps {
+            s = p.grammar.apply(&s);
+        }
+        s.into_iter().collect()
+    }
+
+    #[test]
+    fn test_expansion() {
+        let f = include_bytes!("../test/input.txt");
+        let p = Polymer::parse(&f[..]).unwrap();
+        for steps in 0..8 {
+            let s = grow(&p, steps);
+            let mut e = p.expansion(steps);
+            assert_eq!(e.len(), s.len() as u64);
+            for (k, c) in s.chars().enumerate() {
+                assert_eq!(e.char_at(k as u64), Some(c));
+            }
+            assert_eq!(e.char_at(s.len() as u64), None);
+            assert_eq!(e.slice(3..17), s[3.min(s.len())..17.min(s.len())]);
+        }
+        assert_eq!(p.expansion(4).slice(0..23), "NBBNBNBBCCNBCNCCNBBNBBN");
+
+        let mut e = p.expansion(40);
+        let end = e.len();
+        assert_eq!(end, 3 * (1 << 40) + 1);
+        assert_eq!(e.char_at(0), Some('N'));
+        assert_eq!(e.char_at(end - 1), Some('B'));
+        assert_eq!(e.slice(end - 3..end + 5).len(), 3);
+        assert_eq!(e.slice(1_000_000_000_000..1_000_000_000_020).len(), 20);
+    }
+
+    // every character counted against the polymer itself
+    fn counts(s: &str) -> Vec<(char, BigUint)> {
+        let mut cnt: HashMap<char, BigUint> = HashMap::new();
+        for c in s.chars() {
+            *cnt.entry(c).or_default() += 1u32;
+        }
+        let mut cnt: Vec<_> = cnt.into_iter().collect();
+        cnt.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        cnt
+    }
+
+    #[test]
+    fn test_longer_rules() {
+        let f = include_bytes!("../test/input.txt");
+        let mut input = f.to_vec();
+        input.extend(b"\nNCB -> XY\nBNBB -> Z\nCN -> BB\nCN -> NN\nNBB -> BN");
+        let mut p = Polymer::parse(&input[..]).unwrap();
+        assert_eq!(
+            p.grammar.apply(&p.initial),
+            "NCNBCXYB".chars().collect::<Vec<_>>()
+        );
+        for steps in 0..9 {
+            assert_eq!(p.histogram(), counts(&grow(&p, steps)));
+            let s = grow(&p, steps);
+            let mut e = p.expansion(steps);
+            assert_eq!(e.len(), s.len() as u64);
+            assert_eq!(e.slice(5..40), s[5.min(s.len())..40.min(s.len())]);
+            p.step();
+        }
+
+        let found: Vec<_> = p.grammar.check().iter().map(|f| f.to_string()).collect();
+        assert_eq!(
+            found,
+            [
+                "conflict: CN -> C and CN -> BB",
+                "conflict: CN -> BB and CN -> NN",
+                "note: BNBB overrides BB after it",
+                "note: BNBB overrides NBB after it",
+                "note: NBB overrides BB after it",
+                "note: NCB overrides CB after it",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_little_context() {
+        // whether anything goes between A and B depends on all the As
+        // before it, and there are ever more of them
+        let p = Polymer::parse(&b"AB\n\nAB -> AAB\nBAA -> C"[..]);
+        assert!(p.is_err());
+        // while a little more context settles it here
+        let mut p = Polymer::parse(&b"AAB\n\nCAB -> C\nBA -> C\nAA -> B"[..]).unwrap();
+        for steps in 0..8 {
+            assert_eq!(p.histogram(), counts(&grow(&p, steps)));
+            p.step();
+        }
     }
 }
@@ -0,0 +1,70 @@
+// Square matrices over the integers modulo m.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Matrix {
+    n: usize,
+    m: u64,
+    cells: Vec<u64>,
+}
+
+fn mul_add(acc: u64, a: u64, b: u64, m: u64) -> u64 {
+    ((u128::from(acc) + u128::from(a) * u128::from(b)) % u128::from(m)) as u64
+}
+
+impl Matrix {
+    pub fn zero(n: usize, m: u64) -> Self {
+        Self {
+            n,
+            m,
+            cells: vec![0; n * n],
+        }
+    }
+
+    pub fn add(&mut self, i: usize, j: usize, v: u64) {
+        let c = &mut self.cells[i * self.n + j];
+        *c = mul_add(*c, v, 1, self.m);
+    }
+
+    fn mul(&self, o: &Self) -> Self {
+        let mut ret = Self::zero(self.n, self.m);
+        for i in 0..self.n {
+            for k in 0..self.n {
+                let a = self.cells[i * self.n + k];
+                if a == 0 {
+                    continue;
+                }
+                for j in 0..self.n {
+                    let c = &mut ret.cells[i * self.n + j];
+                    *c = mul_add(*c, a, o.cells[k * self.n + j], self.m);
+                }
+            }
+        }
+        ret
+    }
+
+    // the row vector v times this matrix
+    fn apply(&self, v: &[u64]) -> Vec<u64> {
+        let mut ret = vec![0; self.n];
+        for (i, &a) in v.iter().enumerate().filter(|&(_, &a)| a != 0) {
+            for (j, r) in ret.iter_mut().enumerate() {
+                *r = mul_add(*r, a, self.cells[i * self.n + j], self.m);
+            }
+        }
+        ret
+    }
+
+    // v times this matrix to the power `e`, by repeated squaring
+    pub fn apply_pow(&self, v: &[u64], mut e: u64) -> Vec<u64> {
+        let mut v = v.to_vec();
+        let mut sq = self.clone();
+        while e > 0 {
+            if e & 1 == 1 {
+                v = sq.apply(&v);
+            }
+            e >>= 1;
+            if e > 0 {
+                sq = sq.mul(&sq);
+            }
+        }
+        v
+    }
+}
@@ -0,0 +1,96 @@
+use std::{collections::HashMap, ops::Range};
+
+use crate::grammar::Window;
+
+// Looks into the polymer after some steps without building it. Each window
+// of the template grows independently, so the characters its anchor grows
+// into after n steps are those of the windows it grows into after n - 1.
+pub struct Expansion<'a> {
+    template: Vec<&'a Window>,
+    next: &'a HashMap<Window, Vec<Window>>,
+    steps: usize,
+    // characters grown from the anchor of a window
+    memo: HashMap<(&'a Window, usize), u64>,
+}
+
+impl<'a> Expansion<'a> {
+    pub fn new(template: &[Window], next: &'a HashMap<Window, Vec<Window>>, steps: usize) -> Self {
+        Self {
+            template: template
+                .iter()
+                .map(|w| next.get_key_value(w).unwrap().0)
+                .collect(),
+            next,
+            steps,
+            memo: HashMap::new(),
+        }
+    }
+
+    // lengths saturate, leaving everything past u64::MAX out of reach
+    fn window_len(&mut self, w: &'a Window, n: usize) -> u64 {
+        if n == 0 {
+            return 1;
+        }
+        if let Some(&len) = self.memo.get(&(w, n)) {
+            return len;
+        }
+        let next = self.next;
+        let len = next[w]
+            .iter()
+            .fold(0u64, |len, v| len.saturating_add(self.window_len(v, n - 1)));
+        self.memo.insert((w, n), len);
+        len
+    }
+
+    pub fn len(&mut self) -> u64 {
+        let n = self.steps;
+        self.template
+            .clone()
+            .into_iter()
+            .fold(0, |len, w| len.saturating_add(self.window_len(w, n)))
+    }
+
+    pub fn char_at(&mut self, k: u64) -> Option<char> {
+        let mut s = String::new();
+        self.read(k, 1, &mut s);
+        s.pop()
+    }
+
+    // the characters in `r`, cut short at the end of the polymer
+    pub fn slice(&mut self, r: Range<u64>) -> String {
+        let mut s = String::new();
+        self.read(r.start, r.end.saturating_sub(r.start), &mut s);
+        s
+    }
+
+    fn read(&mut self, mut skip: u64, mut take: u64, out: &mut String) {
+        for w in self.template.clone() {
+            if take == 0 {
+                return;
+            }
+            self.emit(w, self.steps, &mut skip, &mut take, out);
+        }
+    }
+
+    // emits the characters grown from the anchor of `w` after skipping and
+    // up to taking the given numbers of them
+    fn emit(&mut self, w: &'a Window, n: usize, skip: &mut u64, take: &mut u64, out: &mut String) {
+        let len = self.window_len(w, n);
+        if *skip >= len {
+            *skip -= len;
+            return;
+        }
+        if n == 0 {
+            out.push(w.char());
+            *take -= 1;
+            return;
+        }
+        let next = self.next;
+        for v in &next[w] {
+            if *take == 0 {
+                return;
+            }
+            self.emit(v, n - 1, skip, take, out);
+        }
+    }
+}
@@ -1,76 +1,438 @@
-use std::{
-    cmp::{max, min, Reverse},
-    collections::BinaryHeap,
-    io::BufRead,
-};
+use std::{error::Error, io::BufRead};
+
+mod render;
+mod search;
+
+use search::{Algorithm, Moves, Route, Search};
 
 struct Cave {
-    map: Vec<i32>,
+    // None for walls, drawn as '#'
+    map: Vec<Option<i32>>,
     rows: usize,
     cols: usize,
 }
 
 impl Cave {
-    fn parse(f: impl BufRead) -> Self {
+    fn parse(f: impl BufRead) -> Result<Self, Box<dyn Error>> {
         let mut c = Self {
             map: vec![],
             rows: 0,
             cols: 0,
         };
-        for l in f.lines().flatten() {
+        for l in f.lines() {
+            let l = l?;
+            if c.rows > 0 && l.len() != c.cols {
+                return Err(
+                    format!("row {} is {} long, not {}", c.rows + 1, l.len(), c.cols).into(),
+                );
+            }
             c.rows += 1;
             c.cols = l.len();
-            c.map
-                .extend(l.chars().map(|c| c.to_digit(10).unwrap() as i32));
+            for ch in l.chars() {
+                c.map.push(match ch {
+                    '#' => None,
+                    '1'..='9' => Some(ch.to_digit(10).unwrap() as i32),
+                    _ => return Err(format!("bad risk {:?}", ch).into()),
+                });
+            }
         }
-        c
+        Ok(c)
     }
 
-    fn solve(&self, repeat: usize) -> i32 {
-        let mut visited = vec![false; (self.rows * repeat) * (self.cols * repeat)];
-        let mut b = BinaryHeap::from([Reverse((0, (0, 0)))]);
-        while let Some(Reverse((s, (x, y)))) = b.pop() {
-            if (x, y) == (self.cols * repeat - 1, self.rows * repeat - 1) {
-                return s;
-            }
-            let idxs = [
-                (x, min(y + 1, self.rows * repeat - 1)),
-                (x, max(y, 1) - 1),
-                (min(x + 1, self.cols * repeat - 1), y),
-                (max(x, 1) - 1, y),
-            ];
-            for (x, y) in idxs {
-                let idx = x + y * (repeat * self.cols);
-                if !visited[idx] {
-                    visited[idx] = true;
-
-                    let idx = (x % self.cols) + (y % self.rows) * self.cols;
-                    let d = (((x / self.cols + y / self.rows) as i32 + self.map[idx]) - 1) % 9 + 1;
-                    b.push(Reverse((s + d, (x, y))));
-                }
-            }
+    // the map repeated `repeat` times each way, every repeat right or down
+    // one riskier, wrapping from 9 back to 1
+    fn tile(&self, repeat: usize) -> Self {
+        let (rows, cols) = (self.rows * repeat, self.cols * repeat);
+        let map = (0..rows * cols)
+            .map(|i| {
+                let (x, y) = (i % cols, i / cols);
+                let r = self.risk((x % self.cols, y % self.rows))?;
+                Some((r + (x / self.cols + y / self.rows) as i32 - 1) % 9 + 1)
+            })
+            .collect();
+        Self { map, rows, cols }
+    }
+
+    // None for walls and outside the map
+    fn risk(&self, (x, y): (usize, usize)) -> Option<i32> {
+        if x >= self.cols || y >= self.rows {
+            return None;
         }
-        panic!("failed to find solution")
+        self.map[self.index((x, y))]
+    }
+
+    fn index(&self, (x, y): (usize, usize)) -> usize {
+        x + y * self.cols
+    }
+
+    fn pos(&self, i: usize) -> (usize, usize) {
+        (i % self.cols, i / self.cols)
+    }
+
+    fn goal(&self) -> (usize, usize) {
+        (self.cols.saturating_sub(1), self.rows.saturating_sub(1))
+    }
+
+    // top left to bottom right of the tiled map, None if walls cut them off
+    #[allow(dead_code)]
+    fn solve(&self, repeat: usize, moves: Moves, algorithm: Algorithm) -> Option<Route> {
+        let c = self.tile(repeat);
+        Search::new(&c, moves).route(algorithm, (0, 0), c.goal())
     }
 }
 
 fn main() {
-    let c = Cave::parse(std::io::BufReader::new(std::io::stdin()));
-    println!("{}", c.solve(1));
-    println!("{}", c.solve(5));
+    let c = Cave::parse(std::io::BufReader::new(std::io::stdin())).unwrap();
+    // day15 [bench] [--algorithm=dijkstra|astar|bidirectional|dial]
+    //       [--moves=4|8|no-reversal] [--k=n] [--via=x,y]... [--ceiling=risk]
+    //       [--show] [--out=route.png] [--scale=n]
+    let (mut bench, mut algorithm, mut moves) = (false, Algorithm::Dijkstra, Moves::Four);
+    let (mut k, mut stops, mut ceiling) = (1, vec![], None);
+    let (mut show, mut out, mut scale) = (false, None, 4);
+    for a in std::env::args().skip(1) {
+        match a.split_once('=') {
+            None if a == "bench" => bench = true,
+            None if a == "--show" => show = true,
+            Some(("--algorithm", s)) => algorithm = s.parse().unwrap(),
+            Some(("--moves", s)) => moves = s.parse().unwrap(),
+            Some(("--k", n)) => k = n.parse().unwrap(),
+            Some(("--via", p)) => {
+                let (x, y) = p.split_once(',').unwrap();
+                stops.push((x.parse().unwrap(), y.parse().unwrap()));
+            }
+            Some(("--ceiling", r)) => ceiling = Some(r.parse().unwrap()),
+            Some(("--out", f)) => out = Some(f.to_string()),
+            Some(("--scale", n)) => scale = n.parse().unwrap(),
+            _ => panic!("unknown option {}", a),
+        }
+    }
+    if bench {
+        let tiled = c.tile(5);
+        for moves in [Moves::Four, Moves::Eight, Moves::NoReversal] {
+            let s = Search::new(&tiled, moves);
+            for algorithm in search::ALGORITHMS {
+                let t = std::time::Instant::now();
+                let risk = s.route(algorithm, (0, 0), tiled.goal()).map(|r| r.risk);
+                println!(
+                    "{:?} {:?}: {:?} in {:?}",
+                    moves,
+                    algorithm,
+                    risk,
+                    t.elapsed()
+                );
+            }
+        }
+        return;
+    }
+    for repeat in [1, 5] {
+        let tiled = c.tile(repeat);
+        let mut s = Search::new(&tiled, moves);
+        if let Some(r) = ceiling {
+            s = s.ceiling(r);
+        }
+        let (from, to) = ((0, 0), tiled.goal());
+        let routes = if !stops.is_empty() {
+            s.visiting(from, to, &stops).into_iter().collect()
+        } else if k > 1 {
+            s.k_shortest(from, to, k)
+        } else {
+            s.route(algorithm, from, to).into_iter().collect::<Vec<_>>()
+        };
+        if routes.is_empty() {
+            println!("unreachable");
+        }
+        for (i, route) in routes.iter().enumerate() {
+            println!("{}", route.risk);
+            if show {
+                print!("{}", render::text(&tiled, route));
+            }
+            if let Some((stem, ext)) = out.as_ref().and_then(|f| f.rsplit_once('.')) {
+                let f = match i {
+                    0 => format!("{}_{}.{}", stem, repeat, ext),
+                    _ => format!("{}_{}_{}.{}", stem, repeat, i + 1, ext),
+                };
+                let f = std::fs::File::create(f).unwrap();
+                render::png(&tiled, route, std::io::BufWriter::new(f), scale).unwrap();
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashSet;
 
     #[test]
     fn test_cave() {
         let f = include_bytes!("../test/input.txt");
-        let c = Cave::parse(&f[..]);
+        let c = Cave::parse(&f[..]).unwrap();
         assert_eq!(c.rows, 10);
         assert_eq!(c.cols, 10);
-        assert_eq!(c.solve(1), 40);
-        assert_eq!(c.solve(5), 315);
+        assert_eq!(
+            c.solve(1, Moves::Four, Algorithm::Dijkstra).unwrap().risk,
+            40
+        );
+        assert_eq!(
+            c.solve(5, Moves::Four, Algorithm::Dijkstra).unwrap().risk,
+            315
+        );
+        let tiled = c.tile(5);
+        for moves in [Moves::Four, Moves::NoReversal] {
+            let s = Search::new(&tiled, moves);
+            for algorithm in search::ALGORITHMS {
+                let route = s.route(algorithm, (0, 0), tiled.goal()).unwrap();
+                assert_eq!(route.risk, 315);
+            }
+        }
+    }
+
+    // a cave of the given size with risks and some walls from a simple LCG
+    fn random(seed: u32, rows: usize, cols: usize, walls: u32) -> Cave {
+        let mut seed = seed;
+        let map = (0..rows * cols)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let v = (seed >> 16) % 100;
+                (v >= walls).then_some(v as i32 % 9 + 1)
+            })
+            .collect();
+        Cave { map, rows, cols }
+    }
+
+    fn around(c: &Cave, (x, y): (usize, usize), moves: Moves) -> Vec<(usize, usize)> {
+        let mut ret = vec![];
+        for (dx, dy) in [
+            (-1, -1),
+            (-1, 0),
+            (-1, 1),
+            (0, -1),
+            (0, 1),
+            (1, -1),
+            (1, 0),
+            (1, 1),
+        ] {
+            if moves != Moves::Eight && dx != 0 && dy != 0 {
+                continue;
+            }
+            let p = (x as isize + dx, y as isize + dy);
+            if p.0 >= 0 && p.1 >= 0 && c.risk((p.0 as usize, p.1 as usize)).is_some() {
+                ret.push((p.0 as usize, p.1 as usize));
+            }
+        }
+        ret
+    }
+
+    // The lowest risk to every cell, relaxing steps until nothing changes.
+    // Turning straight back never helps, so no-reversal costs the same as
+    // four ways.
+    fn reference(c: &Cave, from: (usize, usize), moves: Moves) -> Vec<Option<i32>> {
+        let mut dist = vec![None; c.map.len()];
+        dist[c.index(from)] = Some(0);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for i in 0..c.map.len() {
+                let Some(d) = dist[i] else { continue };
+                for p in around(c, c.pos(i), moves) {
+                    let (r, j) = (c.risk(p).unwrap(), c.index(p));
+                    if dist[j].is_none_or(|e| d + r < e) {
+                        dist[j] = Some(d + r);
+                        changed = true;
+                    }
+                }
+            }
+        }
+        dist
+    }
+
+    #[test]
+    fn test_random() {
+        for seed in 0..40 {
+            let c = random(seed, 3 + seed as usize % 9, 4 + seed as usize % 7, 25);
+            let from = c.pos(seed as usize * 7 % c.map.len());
+            for moves in [Moves::Four, Moves::Eight, Moves::NoReversal] {
+                let dist = reference(&c, from, moves);
+                let s = Search::new(&c, moves);
+                for (i, &d) in dist.iter().enumerate() {
+                    let to = c.pos(i);
+                    for algorithm in search::ALGORITHMS {
+                        let route = s.route(algorithm, from, to);
+                        if c.risk(from).is_none() || c.risk(to).is_none() {
+                            assert_eq!(route, None);
+                            continue;
+                        }
+                        let what = (algorithm, moves, from, to);
+                        assert_eq!(route.as_ref().map(|r| r.risk), d, "{:?}", what);
+                        let Some(route) = route else { continue };
+                        // the path takes allowed steps and adds up to its risk
+                        assert_eq!(route.path.first(), Some(&from));
+                        assert_eq!(route.path.last(), Some(&to));
+                        for w in route.path.windows(2) {
+                            assert!(around(&c, w[0], moves).contains(&w[1]), "{:?}", what);
+                        }
+                        if moves == Moves::NoReversal {
+                            assert!(route.path.windows(3).all(|w| w[0] != w[2]));
+                        }
+                        let sum: i32 = route.path[1..].iter().map(|&p| c.risk(p).unwrap()).sum();
+                        assert_eq!(sum, route.risk, "{:?}", what);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_unreachable() {
+        let c = Cave::parse(&b"119\n###\n911"[..]).unwrap();
+        assert_eq!(c.solve(1, Moves::Four, Algorithm::Dijkstra), None);
+        assert_eq!(
+            Search::new(&c, Moves::Four)
+                .route(Algorithm::Dijkstra, (0, 0), (2, 0))
+                .unwrap()
+                .risk,
+            10
+        );
+        assert_eq!(
+            Search::new(&c, Moves::Four).route(Algorithm::Dijkstra, (0, 0), (9, 9)),
+            None
+        );
+        assert!(Cave::parse(&b"12\n3"[..]).is_err());
+        assert!(Cave::parse(&b"1x"[..]).is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let c = Cave::parse(&b"1999\n1#99\n1111"[..]).unwrap();
+        let route = c.solve(1, Moves::Four, Algorithm::Dijkstra).unwrap();
+        assert_eq!(route.risk, 5);
+        assert_eq!(render::text(&c, &route), "*999\n*#99\n****\n");
+        let mut png = vec![];
+        render::png(&c, &route, &mut png, 2).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+    }
+
+    // every route from `from` to `to` never entering a cell twice, by risk
+    fn simple_paths(c: &Cave, from: (usize, usize), to: (usize, usize), moves: Moves) -> Vec<i32> {
+        fn walk(
+            c: &Cave,
+            path: &mut Vec<(usize, usize)>,
+            risk: i32,
+            to: (usize, usize),
+            moves: Moves,
+            out: &mut Vec<i32>,
+        ) {
+            let p = *path.last().unwrap();
+            if p == to {
+                out.push(risk);
+                return;
+            }
+            for q in around(c, p, moves) {
+                if !path.contains(&q) {
+                    path.push(q);
+                    walk(c, path, risk + c.risk(q).unwrap(), to, moves, out);
+                    path.pop();
+                }
+            }
+        }
+        let mut out = vec![];
+        walk(c, &mut vec![from], 0, to, moves, &mut out);
+        out.sort();
+        out
+    }
+
+    #[test]
+    fn test_k_shortest() {
+        for seed in 0..30 {
+            let c = random(seed, 3, 3 + seed as usize % 2, 15);
+            let (from, to) = (c.pos(seed as usize % 3), c.goal());
+            for moves in [Moves::Four, Moves::Eight] {
+                let all = if c.risk(from).is_some() && c.risk(to).is_some() {
+                    simple_paths(&c, from, to, moves)
+                } else {
+                    vec![]
+                };
+                let routes = Search::new(&c, moves).k_shortest(from, to, 12);
+                let risks: Vec<_> = routes.iter().map(|r| r.risk).collect();
+                assert_eq!(risks, all[..all.len().min(12)], "{} {:?}", seed, moves);
+                let paths: HashSet<_> = routes.iter().map(|r| &r.path).collect();
+                assert_eq!(paths.len(), routes.len());
+                for r in &routes {
+                    let cells: HashSet<_> = r.path.iter().collect();
+                    assert_eq!(cells.len(), r.path.len());
+                    let sum: i32 = r.path[1..].iter().map(|&p| c.risk(p).unwrap()).sum();
+                    assert_eq!(sum, r.risk);
+                }
+            }
+        }
+
+        let c = Cave::parse(&b"1999\n1#99\n1111"[..]).unwrap();
+        let s = Search::new(&c, Moves::NoReversal);
+        let risks: Vec<_> = s
+            .k_shortest((0, 0), c.goal(), 5)
+            .iter()
+            .map(|r| r.risk)
+            .collect();
+        // simple paths never turn straight back anyway
+        assert_eq!(risks, simple_paths(&c, (0, 0), c.goal(), Moves::Four)[..5]);
+    }
+
+    #[test]
+    fn test_constraints() {
+        for seed in 0..30 {
+            let c = random(seed, 4 + seed as usize % 5, 5, 10);
+            // keeping out of risky cells is the same as walls there
+            let walled = Cave {
+                map: c.map.iter().map(|r| r.filter(|&r| r <= 6)).collect(),
+                ..c
+            };
+            for algorithm in search::ALGORITHMS {
+                let a = Search::new(&c, Moves::Four)
+                    .ceiling(6)
+                    .route(algorithm, (0, 0), c.goal());
+                let b = Search::new(&walled, Moves::Four).route(algorithm, (0, 0), c.goal());
+                assert_eq!(a.map(|r| r.risk), b.map(|r| r.risk));
+            }
+
+            // through the stops in the cheapest order, legs priced apart
+            let stops = [
+                c.pos(seed as usize % 7),
+                c.pos(seed as usize * 3 % 11),
+                (2, 1),
+            ];
+            let from = (0, 0);
+            let leg = |a: (usize, usize), b| reference(&c, a, Moves::Four)[c.index(b)];
+            let mut best = None;
+            for order in [
+                [0, 1, 2],
+                [0, 2, 1],
+                [1, 0, 2],
+                [1, 2, 0],
+                [2, 0, 1],
+                [2, 1, 0],
+            ] {
+                let mut at = from;
+                let mut total = Some(0);
+                for i in order {
+                    total = total.zip(leg(at, stops[i])).map(|(a, b)| a + b);
+                    at = stops[i];
+                }
+                total = total.zip(leg(at, c.goal())).map(|(a, b)| a + b);
+                if c.risk(from).is_none() {
+                    total = None;
+                }
+                best = match (best, total) {
+                    (Some(a), Some(b)) => Some(b.min(a)),
+                    (a, b) => a.or(b),
+                };
+            }
+            let route = Search::new(&c, Moves::Four).visiting(from, c.goal(), &stops);
+            assert_eq!(route.as_ref().map(|r| r.risk), best, "{}", seed);
+            if let Some(r) = route {
+                assert!(stops.iter().all(|p| r.path.contains(p)));
+                let sum: i32 = r.path[1..].iter().map(|&p| c.risk(p).unwrap()).sum();
+                assert_eq!(sum, r.risk);
+            }
+        }
     }
 }
@@ -0,0 +1,46 @@
+use std::{collections::HashSet, error::Error, io::Write};
+
+use crate::{search::Route, Cave};
+
+// The map with the route drawn over it as `*`.
+pub fn text(cave: &Cave, route: &Route) -> String {
+    let on: HashSet<_> = route.path.iter().collect();
+    let mut s = String::new();
+    for y in 0..cave.rows {
+        for x in 0..cave.cols {
+            s.push(match cave.risk((x, y)) {
+                _ if on.contains(&(x, y)) => '*',
+                Some(r) => char::from_digit(r as u32, 10).unwrap(),
+                None => '#',
+            });
+        }
+        s.push('\n');
+    }
+    s
+}
+
+// Risks in shades of grey, darker for riskier cells and black for walls,
+// with the route in red. Every cell is a `scale` x `scale` block.
+pub fn png(cave: &Cave, route: &Route, w: impl Write, scale: usize) -> Result<(), Box<dyn Error>> {
+    let scale = scale.max(1);
+    let on: HashSet<_> = route.path.iter().collect();
+    let (width, height) = (cave.cols * scale, cave.rows * scale);
+    let mut data = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        for x in 0..width {
+            let p = (x / scale, y / scale);
+            data.extend(match cave.risk(p) {
+                _ if on.contains(&p) => [220, 30, 30],
+                Some(r) => [255 - (r * 24) as u8; 3],
+                None => [0; 3],
+            });
+        }
+    }
+    let mut encoder = png::Encoder::new(w, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&data)?;
+    writer.finish()?;
+    Ok(())
+}
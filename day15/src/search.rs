@@ -0,0 +1,478 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+    str::FromStr,
+};
+
+use crate::Cave;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    // the risk of every cell entered, so not that of the first
+    pub risk: i32,
+    // (x, y) of every cell from start to goal
+    pub path: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Moves {
+    Four,
+    // diagonal steps too, at the risk of the cell entered like any other
+    Eight,
+    // four ways, never straight back to the cell just left
+    NoReversal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Dijkstra,
+    // Dijkstra ordered by risk so far plus the least risk of the steps left
+    AStar,
+    // Dijkstra from both ends until the two can no longer meet any cheaper
+    Bidirectional,
+    // Dijkstra with a bucket for every risk, which fits risks of 1 to 9
+    Dial,
+}
+
+pub const ALGORITHMS: [Algorithm; 4] = [
+    Algorithm::Dijkstra,
+    Algorithm::AStar,
+    Algorithm::Bidirectional,
+    Algorithm::Dial,
+];
+
+impl FromStr for Moves {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "4" => Ok(Self::Four),
+            "8" => Ok(Self::Eight),
+            "no-reversal" => Ok(Self::NoReversal),
+            _ => Err(format!(
+                "unknown moves {:?}, expected 4, 8 or no-reversal",
+                s
+            )),
+        }
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dijkstra" => Ok(Self::Dijkstra),
+            "astar" => Ok(Self::AStar),
+            "bidirectional" => Ok(Self::Bidirectional),
+            "dial" => Ok(Self::Dial),
+            _ => Err(format!("unknown algorithm {:?}", s)),
+        }
+    }
+}
+
+// opposite steps side by side, so step k undoes step k ^ 1
+const STEPS: [(isize, isize); 8] = [
+    (-1, 0),
+    (1, 0),
+    (0, -1),
+    (0, 1),
+    (-1, -1),
+    (1, 1),
+    (-1, 1),
+    (1, -1),
+];
+
+// Searches run over states rather than cells, a state being a cell and,
+// where the moves care, the step that led there. State cell * width + k
+// came by step k, and state cell * width + 4 is where the route starts.
+#[derive(Clone)]
+pub struct Search<'a> {
+    cave: &'a Cave,
+    moves: Moves,
+    width: usize,
+    // cells to keep out of, by index
+    blocked: Vec<bool>,
+    // steps not to take, by cell index
+    cut: HashSet<(usize, usize)>,
+}
+
+const NONE: usize = usize::MAX;
+
+impl<'a> Search<'a> {
+    pub fn new(cave: &'a Cave, moves: Moves) -> Self {
+        let width = if moves == Moves::NoReversal { 5 } else { 1 };
+        Self {
+            cave,
+            moves,
+            width,
+            blocked: vec![false; cave.map.len()],
+            cut: HashSet::new(),
+        }
+    }
+
+    // keeps out of cells riskier than `risk`
+    pub fn ceiling(mut self, risk: i32) -> Self {
+        for (b, r) in self.blocked.iter_mut().zip(&self.cave.map) {
+            *b |= r.is_some_and(|r| r > risk);
+        }
+        self
+    }
+
+    fn open(&self, p: (usize, usize)) -> bool {
+        self.cave.risk(p).is_some() && !self.blocked[self.cave.index(p)]
+    }
+
+    fn steps(&self) -> &'static [(isize, isize)] {
+        match self.moves {
+            Moves::Eight => &STEPS,
+            _ => &STEPS[..4],
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.cave.map.len() * self.width
+    }
+
+    fn cell(&self, s: usize) -> (usize, usize) {
+        self.cave.pos(s / self.width)
+    }
+
+    fn start(&self, from: (usize, usize)) -> usize {
+        self.state(from, 4)
+    }
+
+    fn goals(&self, to: (usize, usize)) -> impl Iterator<Item = usize> {
+        let i = self.cave.index(to) * self.width;
+        i..i + self.width
+    }
+
+    fn step(&self, (x, y): (usize, usize), (dx, dy): (isize, isize)) -> Option<(usize, usize)> {
+        let p = (x.checked_add_signed(dx)?, y.checked_add_signed(dy)?);
+        self.open(p).then_some(p)
+    }
+
+    fn state(&self, p: (usize, usize), k: usize) -> usize {
+        self.cave.index(p) * self.width + if self.width > 1 { k } else { 0 }
+    }
+
+    // the states a step from `s`, with the risk of taking it
+    fn next(&self, s: usize) -> impl Iterator<Item = (usize, i32)> + '_ {
+        let (p, came) = (self.cell(s), s % self.width);
+        self.steps()
+            .iter()
+            .enumerate()
+            .filter(move |&(k, _)| self.width == 1 || came == 4 || k != came ^ 1)
+            .filter_map(move |(k, &d)| {
+                let q = self.step(p, d)?;
+                if self.cut.contains(&(self.cave.index(p), self.cave.index(q))) {
+                    return None;
+                }
+                Some((self.state(q, k), self.cave.risk(q)?))
+            })
+    }
+
+    // the states a step before `s`, with the risk of taking it
+    fn prev(&self, s: usize) -> impl Iterator<Item = (usize, i32)> + '_ {
+        let (p, came) = (self.cell(s), s % self.width);
+        let risk = self.cave.risk(p).unwrap_or(0);
+        self.steps()
+            .iter()
+            .enumerate()
+            .filter(move |&(k, _)| self.width == 1 || k == came)
+            .filter_map(move |(_, &(dx, dy))| self.step(p, (-dx, -dy)))
+            .filter(move |&q| !self.cut.contains(&(self.cave.index(q), self.cave.index(p))))
+            .flat_map(move |q| {
+                let before =
+                    (0..self.width).filter(move |&k| self.width == 1 || k == 4 || k != came ^ 1);
+                before.map(move |k| (self.cave.index(q) * self.width + k, risk))
+            })
+    }
+
+    // a lower bound on the risk from `s` to `to`
+    fn heuristic(&self, s: usize, to: (usize, usize), least: i32) -> i32 {
+        let (p, q) = (self.cell(s), to);
+        let (dx, dy) = (p.0.abs_diff(q.0), p.1.abs_diff(q.1));
+        let steps = match self.moves {
+            Moves::Eight => dx.max(dy),
+            _ => dx + dy,
+        };
+        steps as i32 * least
+    }
+
+    pub fn route(
+        &self,
+        algorithm: Algorithm,
+        from: (usize, usize),
+        to: (usize, usize),
+    ) -> Option<Route> {
+        if !self.open(from) || !self.open(to) {
+            return None;
+        }
+        match algorithm {
+            Algorithm::Dijkstra => self.best_first(self.start(from), to, |_| 0),
+            Algorithm::AStar => {
+                let least = self.cave.map.iter().flatten().min().copied().unwrap_or(0);
+                self.best_first(self.start(from), to, |s| self.heuristic(s, to, least))
+            }
+            Algorithm::Bidirectional => self.bidirectional(from, to),
+            Algorithm::Dial => self.dial(from, to),
+        }
+    }
+
+    // Dijkstra, or A* given a heuristic. A state is settled when it leaves
+    // the queue, and entries for states found a cheaper way since are
+    // skipped.
+    fn best_first(
+        &self,
+        start: usize,
+        to: (usize, usize),
+        h: impl Fn(usize) -> i32,
+    ) -> Option<Route> {
+        let mut dist = vec![i32::MAX; self.len()];
+        let mut prev = vec![NONE; self.len()];
+        dist[start] = 0;
+        let mut q = BinaryHeap::from([Reverse((h(start), start))]);
+        while let Some(Reverse((f, s))) = q.pop() {
+            let d = dist[s];
+            if f - h(s) > d {
+                continue;
+            }
+            if self.cell(s) == to {
+                return Some(self.trace(&prev, s, d));
+            }
+            for (t, r) in self.next(s) {
+                if d + r < dist[t] {
+                    dist[t] = d + r;
+                    prev[t] = s;
+                    q.push(Reverse((d + r + h(t), t)));
+                }
+            }
+        }
+        None
+    }
+
+    fn dial(&self, from: (usize, usize), to: (usize, usize)) -> Option<Route> {
+        let most = self.cave.map.iter().flatten().max().copied().unwrap_or(0);
+        // distances d sit in bucket d % n, none more than `most` ahead of
+        // the one being emptied
+        let n = most as usize + 1;
+        let mut buckets = vec![vec![]; n];
+        let mut dist = vec![i32::MAX; self.len()];
+        let mut prev = vec![NONE; self.len()];
+        let start = self.start(from);
+        dist[start] = 0;
+        buckets[0].push(start);
+        let (mut d, mut queued) = (0, 1);
+        while queued > 0 {
+            let Some(s) = buckets[d as usize % n].pop() else {
+                d += 1;
+                continue;
+            };
+            queued -= 1;
+            if dist[s] != d {
+                continue;
+            }
+            if self.cell(s) == to {
+                return Some(self.trace(&prev, s, d));
+            }
+            for (t, r) in self.next(s) {
+                if d + r < dist[t] {
+                    dist[t] = d + r;
+                    prev[t] = s;
+                    buckets[(d + r) as usize % n].push(t);
+                    queued += 1;
+                }
+            }
+        }
+        None
+    }
+
+    // Once the least risks left in the two queues add up to no less than
+    // the best meeting found, nothing cheaper can turn up.
+    fn bidirectional(&self, from: (usize, usize), to: (usize, usize)) -> Option<Route> {
+        if from == to {
+            return Some(Route {
+                risk: 0,
+                path: vec![from],
+            });
+        }
+        let mut dist = [vec![i32::MAX; self.len()], vec![i32::MAX; self.len()]];
+        // the state before on the way out, after on the way back
+        let mut link = [vec![NONE; self.len()], vec![NONE; self.len()]];
+        let mut q: [BinaryHeap<Reverse<(i32, usize)>>; 2] = Default::default();
+        let start = self.start(from);
+        dist[0][start] = 0;
+        q[0].push(Reverse((0, start)));
+        for g in self.goals(to) {
+            dist[1][g] = 0;
+            q[1].push(Reverse((0, g)));
+        }
+        let (mut best, mut meet) = (i32::MAX, NONE);
+        while let (Some(&Reverse((a, _))), Some(&Reverse((b, _)))) = (q[0].peek(), q[1].peek()) {
+            if a.saturating_add(b) >= best {
+                break;
+            }
+            let side = usize::from(b < a);
+            let Reverse((d, s)) = q[side].pop().unwrap();
+            if d > dist[side][s] {
+                continue;
+            }
+            let steps: Vec<_> = if side == 0 {
+                self.next(s).collect()
+            } else {
+                self.prev(s).collect()
+            };
+            for (t, r) in steps {
+                if d + r < dist[side][t] {
+                    dist[side][t] = d + r;
+                    link[side][t] = s;
+                    q[side].push(Reverse((d + r, t)));
+                }
+                let other = dist[1 - side][t];
+                if other != i32::MAX && d + r + other < best {
+                    best = d + r + other;
+                    meet = t;
+                }
+            }
+        }
+        if meet == NONE {
+            return None;
+        }
+        let mut route = self.trace(&link[0], meet, best);
+        let mut s = meet;
+        while link[1][s] != NONE {
+            s = link[1][s];
+            route.path.push(self.cell(s));
+        }
+        Some(route)
+    }
+
+    // the state reached at the end of `path`
+    fn arrive(&self, path: &[(usize, usize)]) -> usize {
+        let &[.., (x0, y0), (x1, y1)] = path else {
+            return self.start(path[0]);
+        };
+        let d = (x1 as isize - x0 as isize, y1 as isize - y0 as isize);
+        self.state((x1, y1), STEPS.iter().position(|&s| s == d).unwrap())
+    }
+
+    // Yen's algorithm: the k lowest risk routes that never enter a cell
+    // twice, lowest first. Each next one leaves some earlier one somewhere
+    // along it, by a step none of those sharing the way there took. Under
+    // no-reversal the cheapest way on can have to pass a cell twice, and is
+    // then dropped, so routes can go missing there.
+    pub fn k_shortest(&self, from: (usize, usize), to: (usize, usize), k: usize) -> Vec<Route> {
+        let mut found = vec![];
+        let Some(first) = self.route(Algorithm::Dijkstra, from, to).filter(|_| k > 0) else {
+            return found;
+        };
+        let mut s = self.clone();
+        let mut candidates = BinaryHeap::new();
+        let mut seen = HashSet::from([first.path.clone()]);
+        found.push(first);
+        while found.len() < k {
+            let last = found[found.len() - 1].path.clone();
+            for j in 0..last.len() - 1 {
+                let root = &last[..=j];
+                for r in &found {
+                    if r.path.len() > j + 1 && r.path[..=j] == *root {
+                        let (a, b) = (self.cave.index(r.path[j]), self.cave.index(r.path[j + 1]));
+                        s.cut.insert((a, b));
+                    }
+                }
+                for &p in &root[..j] {
+                    s.blocked[self.cave.index(p)] = true;
+                }
+                if let Some(spur) = s.best_first(s.arrive(root), to, |_| 0) {
+                    let path: Vec<_> = root[..j].iter().chain(&spur.path).copied().collect();
+                    let risk = root[1..]
+                        .iter()
+                        .map(|&p| self.cave.risk(p).unwrap())
+                        .sum::<i32>()
+                        + spur.risk;
+                    let cells: HashSet<_> = path.iter().collect();
+                    if cells.len() == path.len() && seen.insert(path.clone()) {
+                        candidates.push(Reverse((risk, path)));
+                    }
+                }
+                s.cut.clear();
+                for &p in &root[..j] {
+                    s.blocked[self.cave.index(p)] = false;
+                }
+            }
+            let Some(Reverse((risk, path))) = candidates.pop() else {
+                break;
+            };
+            found.push(Route { risk, path });
+        }
+        found
+    }
+
+    // The lowest risk route through every one of `stops` in any order,
+    // by Dijkstra over states paired with the stops passed so far. Routes
+    // may pass a cell more than once.
+    pub fn visiting(
+        &self,
+        from: (usize, usize),
+        to: (usize, usize),
+        stops: &[(usize, usize)],
+    ) -> Option<Route> {
+        if !self.open(from) || !self.open(to) || !stops.iter().all(|&p| self.open(p)) {
+            return None;
+        }
+        let bits: HashMap<_, _> =
+            stops
+                .iter()
+                .enumerate()
+                .fold(HashMap::new(), |mut m, (i, &p)| {
+                    *m.entry(p).or_insert(0usize) |= 1 << i;
+                    m
+                });
+        let (layers, all) = (1 << stops.len(), (1 << stops.len()) - 1);
+        let bit = |p| bits.get(&p).copied().unwrap_or(0);
+
+        let mut dist = vec![i32::MAX; self.len() * layers];
+        let mut prev = vec![NONE; self.len() * layers];
+        let start = self.start(from) * layers + bit(from);
+        dist[start] = 0;
+        let mut q = BinaryHeap::from([Reverse((0, start))]);
+        while let Some(Reverse((d, i))) = q.pop() {
+            if d > dist[i] {
+                continue;
+            }
+            let (s, passed) = (i / layers, i % layers);
+            if self.cell(s) == to && passed == all {
+                let mut path = vec![self.cell(s)];
+                let mut i = i;
+                while prev[i] != NONE {
+                    i = prev[i];
+                    path.push(self.cell(i / layers));
+                }
+                path.reverse();
+                return Some(Route { risk: d, path });
+            }
+            for (t, r) in self.next(s) {
+                let j = t * layers + (passed | bit(self.cell(t)));
+                if d + r < dist[j] {
+                    dist[j] = d + r;
+                    prev[j] = i;
+                    q.push(Reverse((d + r, j)));
+                }
+            }
+        }
+        None
+    }
+
+    // follows `prev` back from `end`
+    fn trace(&self, prev: &[usize], end: usize, risk: i32) -> Route {
+        let mut path = vec![self.cell(end)];
+        let mut s = end;
+        while prev[s] != NONE {
+            s = prev[s];
+            path.push(self.cell(s));
+        }
+        path.reverse();
+        Route { risk, path }
+    }
+}
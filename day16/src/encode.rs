@@ -0,0 +1,152 @@
+use std::{error::Error, str::FromStr};
+
+use crate::Packet;
+
+// How operator packets give the extent of their sub-packets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Length {
+    // type 0, the number of bits they take in 15 bits
+    Bits,
+    // type 1, how many there are in 11 bits
+    Count,
+}
+
+impl FromStr for Length {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bits" => Ok(Self::Bits),
+            "count" => Ok(Self::Count),
+            _ => Err(format!(
+                "unknown length type {:?}, expected bits or count",
+                s
+            )),
+        }
+    }
+}
+
+// bits written most significant first
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl Writer {
+    fn push(&mut self, value: u64, width: usize) {
+        for i in (0..width).rev() {
+            if self.len.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+            if value >> i & 1 == 1 {
+                *self.bytes.last_mut().unwrap() |= 0x80 >> (self.len % 8);
+            }
+            self.len += 1;
+        }
+    }
+
+    fn append(&mut self, other: &Writer) {
+        for i in 0..other.len {
+            self.push(u64::from(other.bytes[i / 8] >> (7 - i % 8) & 1), 1);
+        }
+    }
+}
+
+impl Packet {
+    // the packet in BITS, padded with zeros to whole bytes
+    pub fn encode(&self, length: Length) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut w = Writer::default();
+        self.write(&mut w, length)?;
+        Ok(w.bytes)
+    }
+
+    pub fn to_hex(&self, length: Length) -> Result<String, Box<dyn Error>> {
+        Ok(hex::encode_upper(self.encode(length)?))
+    }
+
+    fn write(&self, w: &mut Writer, length: Length) -> Result<(), Box<dyn Error>> {
+        let version = match self {
+            Packet::Literal { version, .. } | Packet::Operation { version, .. } => *version,
+        };
+        if version > 7 {
+            return Err(format!("version {} does not fit 3 bits", version).into());
+        }
+        w.push(version.into(), 3);
+        match self {
+            Packet::Literal { value, .. } => {
+                if *value < 0 {
+                    return Err(format!("literal {} is negative", value).into());
+                }
+                // groups of 4 bits, all but the last flagged with a 1
+                let groups = (64 - value.leading_zeros() as usize).div_ceil(4).max(1);
+                w.push(4, 3);
+                for i in (0..groups).rev() {
+                    w.push(u64::from(i > 0), 1);
+                    w.push((value >> (4 * i) & 0xf) as u64, 4);
+                }
+            }
+            Packet::Operation { op, sub, .. } => {
+                if *op > 7 || *op == 4 {
+                    return Err(format!("type {} is not an operator", op).into());
+                }
+                w.push((*op).into(), 3);
+                match length {
+                    Length::Bits => {
+                        let mut inner = Writer::default();
+                        for s in sub {
+                            s.write(&mut inner, length)?;
+                        }
+                        if inner.len >= 1 << 15 {
+                            return Err(format!(
+                                "{} bits of sub-packets do not fit 15 bits",
+                                inner.len
+                            )
+                            .into());
+                        }
+                        w.push(0, 1);
+                        w.push(inner.len as u64, 15);
+                        w.append(&inner);
+                    }
+                    Length::Count => {
+                        if sub.len() >= 1 << 11 {
+                            return Err(
+                                format!("{} sub-packets do not fit 11 bits", sub.len()).into()
+                            );
+                        }
+                        w.push(1, 1);
+                        w.push(sub.len() as u64, 11);
+                        for s in sub {
+                            s.write(w, length)?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn next(seed: &mut u32, n: u32) -> u32 {
+    *seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+    (*seed >> 16) % n
+}
+
+// A packet made up from a simple LCG, with operators nested at most `depth`
+// deep. Comparisons get the two sub-packets they need and the rest one to
+// four.
+pub fn random(seed: &mut u32, depth: usize) -> Packet {
+    let version = next(seed, 8) as u8;
+    if depth == 0 || next(seed, 3) == 0 {
+        let value = match next(seed, 3) {
+            0 => i64::from(next(seed, 16)),
+            1 => i64::from(next(seed, 1 << 15)),
+            _ => i64::from(next(seed, 1 << 15)) << 40 | i64::from(next(seed, 1 << 15)),
+        };
+        return Packet::Literal { version, value };
+    }
+    let op = [0, 1, 2, 3, 5, 6, 7][next(seed, 7) as usize];
+    let n = if op >= 5 { 2 } else { next(seed, 4) + 1 };
+    let sub = (0..n).map(|_| random(seed, depth - 1)).collect();
+    Packet::Operation { version, op, sub }
+}
@@ -0,0 +1,188 @@
+use std::{error::Error, fmt};
+
+use nom::{
+    branch::alt,
+    bytes::complete::is_not,
+    character::complete::{alpha1, char, digit1, multispace1},
+    combinator::{map, opt, recognize},
+    multi::{many0, separated_list0},
+    sequence::{delimited, pair, preceded, terminated, tuple},
+    IResult,
+};
+
+use crate::Packet;
+
+// Operators by type ID. Type 4 is a literal, never written as a call.
+const NAMES: [&str; 8] = ["sum", "prod", "min", "max", "", "gt", "lt", "eq"];
+
+// A call or a number as written, checked once the whole text has parsed.
+enum Expr<'a> {
+    Number(&'a str, Option<&'a str>),
+    Call(&'a str, Option<&'a str>, Vec<Expr<'a>>),
+}
+
+// whitespace and comments from `#` to the end of the line
+fn space(input: &str) -> IResult<&str, ()> {
+    map(
+        many0(alt((
+            multispace1,
+            recognize(pair(char('#'), opt(is_not("\n")))),
+        ))),
+        |_| (),
+    )(input)
+}
+
+fn version(input: &str) -> IResult<&str, Option<&str>> {
+    opt(preceded(char('@'), digit1))(input)
+}
+
+fn expr(input: &str) -> IResult<&str, Expr<'_>> {
+    delimited(
+        space,
+        alt((
+            map(pair(digit1, version), |(n, v)| Expr::Number(n, v)),
+            map(
+                tuple((
+                    alpha1,
+                    version,
+                    preceded(space, char('(')),
+                    separated_list0(char(','), expr),
+                    terminated(space, char(')')),
+                )),
+                |(name, v, _, args, _)| Expr::Call(name, v, args),
+            ),
+        )),
+        space,
+    )(input)
+}
+
+fn build(e: Expr) -> Result<Packet, Box<dyn Error>> {
+    let version = |v: Option<&str>| -> Result<u8, Box<dyn Error>> {
+        match v.map(str::parse::<u8>).transpose()? {
+            Some(v) if v > 7 => Err(format!("version {} does not fit 3 bits", v).into()),
+            v => Ok(v.unwrap_or(0)),
+        }
+    };
+    Ok(match e {
+        Expr::Number(n, v) => Packet::Literal {
+            version: version(v)?,
+            value: n.parse()?,
+        },
+        Expr::Call(name, v, args) => {
+            let op = NAMES
+                .iter()
+                .position(|&n| n == name && !n.is_empty())
+                .ok_or_else(|| format!("unknown operator {:?}", name))?;
+            match (op, args.len()) {
+                (2 | 3, 0) => return Err(format!("{}() needs an argument", name).into()),
+                (5..=7, n) if n != 2 => {
+                    return Err(format!("{}() takes two arguments, not {}", name, n).into())
+                }
+                _ => {}
+            }
+            Packet::Operation {
+                version: version(v)?,
+                op: op as u8,
+                sub: args.into_iter().map(build).collect::<Result<_, _>>()?,
+            }
+        }
+    })
+}
+
+// Reads `sum(1, prod(2, 3), max(4, 5), gt(7, 2))`, versions given as in
+// `sum@3(1, 2@5)` and 0 where left out.
+pub fn compile(src: &str) -> Result<Packet, Box<dyn Error>> {
+    match expr(src) {
+        Ok(("", e)) => build(e),
+        Ok((rest, _)) | Err(nom::Err::Error(nom::error::Error { input: rest, .. })) => {
+            let at = src.len() - rest.len();
+            let line = src[..at].matches('\n').count() + 1;
+            let column = at - src[..at].rfind('\n').map_or(0, |i| i + 1) + 1;
+            Err(format!(
+                "unexpected {:?} at {}:{}",
+                rest.chars().next().map_or("end".into(), String::from),
+                line,
+                column
+            )
+            .into())
+        }
+        Err(e) => Err(e.to_string().into()),
+    }
+}
+
+// versions after the name or number, where not 0
+fn suffix(version: u8) -> String {
+    match version {
+        0 => String::new(),
+        v => format!("@{}", v),
+    }
+}
+
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Packet::Literal { version, value } => write!(f, "{}{}", value, suffix(*version)),
+            Packet::Operation { version, op, sub } => {
+                let name = NAMES.get(*op as usize).copied().unwrap_or("");
+                write!(f, "{}{}(", name, suffix(*version))?;
+                for (i, s) in sub.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", s)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl Packet {
+    // Calls that do not fit in `width` get a line for every argument. With
+    // `annotate`, calls end their first line with a comment giving their
+    // value, `?` where it cannot be worked out.
+    pub fn pretty(&self, width: usize, annotate: bool) -> String {
+        let mut out = String::new();
+        self.pretty_into(&mut out, 0, width, annotate, "");
+        out
+    }
+
+    fn pretty_into(
+        &self,
+        out: &mut String,
+        indent: usize,
+        width: usize,
+        annotate: bool,
+        trailer: &str,
+    ) {
+        let note = match self {
+            Packet::Operation { .. } if annotate => match self.try_eval() {
+                Some(v) => format!("  # = {}", v),
+                None => "  # = ?".to_string(),
+            },
+            _ => String::new(),
+        };
+        let flat = self.to_string();
+        out.push_str(&" ".repeat(indent));
+        match self {
+            Packet::Operation { version, op, sub }
+                if indent + flat.len() + trailer.len() > width =>
+            {
+                let name = NAMES.get(*op as usize).copied().unwrap_or("");
+                out.push_str(&format!("{}{}({}\n", name, suffix(*version), note));
+                for (i, s) in sub.iter().enumerate() {
+                    let last = i + 1 == sub.len();
+                    s.pretty_into(
+                        out,
+                        indent + 2,
+                        width,
+                        annotate,
+                        if last { "" } else { "," },
+                    );
+                }
+                out.push_str(&format!("{}){}\n", " ".repeat(indent), trailer));
+            }
+            _ => out.push_str(&format!("{}{}{}\n", flat, trailer, note)),
+        }
+    }
+}
@@ -7,7 +7,12 @@ use nom::{
     IResult,
 };
 
-#[derive(Debug)]
+mod encode;
+mod expr;
+
+use encode::Length;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 enum Packet {
     Literal {
         version: u8,
@@ -83,30 +88,36 @@ impl Packet {
     }
 
     fn eval(&self) -> i64 {
+        self.try_eval().expect("invalid operation")
+    }
+
+    // None for operations missing sub-packets, unknown ones and overflow
+    fn try_eval(&self) -> Option<i64> {
         match self {
-            Packet::Literal { version: _, value } => *value,
+            Packet::Literal { version: _, value } => Some(*value),
             Packet::Operation {
                 version: _,
                 op,
                 sub,
             } => {
-                let mut sub = sub.iter().map(Self::eval);
+                let sub = sub.iter().map(Self::try_eval).collect::<Option<Vec<_>>>()?;
+                let mut sub = sub.into_iter();
                 match op {
                     // sum
-                    0 => sub.sum(),
+                    0 => sub.try_fold(0i64, i64::checked_add),
                     // prod
-                    1 => sub.product(),
+                    1 => sub.try_fold(1i64, i64::checked_mul),
                     // min
-                    2 => sub.min().unwrap(),
+                    2 => sub.min(),
                     // max
-                    3 => sub.max().unwrap(),
+                    3 => sub.max(),
                     // gt
-                    5 => i64::from(sub.next().unwrap() > sub.next().unwrap()),
+                    5 => Some(i64::from(sub.next()? > sub.next()?)),
                     // lt
-                    6 => i64::from(sub.next().unwrap() < sub.next().unwrap()),
+                    6 => Some(i64::from(sub.next()? < sub.next()?)),
                     // eq
-                    7 => i64::from(sub.next().unwrap() == sub.next().unwrap()),
-                    _ => panic!("invalid operation"),
+                    7 => Some(i64::from(sub.next()? == sub.next()?)),
+                    _ => None,
                 }
             }
         }
@@ -114,10 +125,50 @@ impl Packet {
 }
 
 fn main() {
-    for l in std::io::BufReader::new(std::io::stdin()).lines().flatten() {
-        let p = Packet::parse(&l);
-        println!("{}", p.sum_version());
-        println!("{}", p.eval());
+    // day16 [reencode | random <seed> [depth] | compile | decompile]
+    //       [--length=bits|count] [--width=n] [--annotate]
+    let (mut args, mut length) = (vec![], Length::Bits);
+    let (mut width, mut annotate) = (80, false);
+    for a in std::env::args().skip(1) {
+        match a.split_once('=') {
+            Some(("--length", l)) => length = l.parse().unwrap(),
+            Some(("--width", n)) => width = n.parse().unwrap(),
+            None if a == "--annotate" => annotate = true,
+            _ => args.push(a),
+        }
+    }
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [] => {
+            for l in std::io::BufReader::new(std::io::stdin()).lines() {
+                let p = Packet::parse(&l.unwrap());
+                println!("{}", p.sum_version());
+                println!("{}", p.eval());
+            }
+        }
+        ["reencode"] => {
+            for l in std::io::BufReader::new(std::io::stdin()).lines() {
+                let p = Packet::parse(&l.unwrap());
+                println!("{}", p.to_hex(length).unwrap());
+            }
+        }
+        ["compile"] => {
+            let src = std::io::read_to_string(std::io::stdin()).unwrap();
+            match expr::compile(&src) {
+                Ok(p) => println!("{}", p.to_hex(length).unwrap()),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        ["decompile"] => {
+            for l in std::io::BufReader::new(std::io::stdin()).lines() {
+                print!("{}", Packet::parse(&l.unwrap()).pretty(width, annotate));
+            }
+        }
+        ["random", seed, ref depth @ ..] => {
+            let depth = depth.first().map_or(4, |d| d.parse().unwrap());
+            let p = encode::random(&mut seed.parse().unwrap(), depth);
+            println!("{}", p.to_hex(length).unwrap());
+        }
+        _ => panic!("bad arguments {:?}", args),
     }
 }
 
@@ -148,3 +199,101 @@ fn test_eval() {
     assert_eq!(Packet::parse("9C005AC2F8F0").eval(), 0);
     assert_eq!(Packet::parse("9C0141080250320F1802104A08").eval(), 1);
 }
+
+#[test]
+fn test_encode() {
+    // the examples, packed just as they were
+    for hex in ["D2FE28", "38006F45291200"] {
+        assert_eq!(Packet::parse(hex).to_hex(Length::Bits).unwrap(), hex);
+    }
+    for hex in ["EE00D40C823060", "C200B40A82"] {
+        assert_eq!(Packet::parse(hex).to_hex(Length::Count).unwrap(), hex);
+    }
+
+    let literal = |version, value| Packet::Literal { version, value };
+    assert!(literal(8, 1).encode(Length::Bits).is_err());
+    assert!(literal(1, -1).encode(Length::Bits).is_err());
+    let op = |op, n| Packet::Operation {
+        version: 0,
+        op,
+        sub: vec![literal(0, 0); n],
+    };
+    assert!(op(4, 1).encode(Length::Bits).is_err());
+    assert!(op(0, 2048).encode(Length::Count).is_err());
+    assert!(op(0, 2047).encode(Length::Count).is_ok());
+    // a literal 0 takes 11 bits
+    assert!(op(0, 2978).encode(Length::Bits).is_ok());
+    assert!(op(0, 2979).encode(Length::Bits).is_err());
+    assert_eq!(op(0, 0).to_hex(Length::Bits).unwrap(), "000000");
+    assert_eq!(literal(7, i64::MAX).to_hex(Length::Bits).unwrap().len(), 22);
+}
+
+#[test]
+fn test_round_trip() {
+    let mut seed = 1;
+    for _ in 0..300 {
+        let p = encode::random(&mut seed, 5);
+        for length in [Length::Bits, Length::Count] {
+            let hex = p.to_hex(length).unwrap();
+            assert_eq!(Packet::parse(&hex), p, "{}", hex);
+        }
+    }
+    let p = Packet::Literal {
+        version: 7,
+        value: i64::MAX,
+    };
+    assert_eq!(Packet::parse(&p.to_hex(Length::Count).unwrap()), p);
+}
+
+#[test]
+fn test_compile() {
+    let p = expr::compile("sum(1, prod(2, 3), max(4, 5), gt(7, 2))").unwrap();
+    assert_eq!(p.eval(), 13);
+    let hex = p.to_hex(Length::Count).unwrap();
+    assert_eq!(Packet::parse(&hex), p);
+    assert_eq!(p.to_string(), "sum(1, prod(2, 3), max(4, 5), gt(7, 2))");
+
+    let p = expr::compile(" eq@6 ( 1@2,# one\n min(3) )\n").unwrap();
+    assert_eq!(p.to_string(), "eq@6(1@2, min(3))");
+    assert_eq!(p.eval(), 0);
+    assert_eq!(
+        Packet::parse("9C0141080250320F1802104A08").to_string(),
+        "eq@4(sum@2(1@2, 3@4), prod@6(2, 2@2))"
+    );
+
+    for bad in [
+        "sum(1, 2",
+        "sum(1, 2))",
+        "add(1)",
+        "gt(1)",
+        "min()",
+        "1@8",
+        "99999999999999999999",
+        "",
+    ] {
+        assert!(expr::compile(bad).is_err(), "{}", bad);
+    }
+    assert_eq!(
+        expr::compile("sum(1,\n  2 3)").unwrap_err().to_string(),
+        "unexpected \"3\" at 2:5"
+    );
+}
+
+#[test]
+fn test_decompile() {
+    let p = expr::compile("sum(1, prod(2, 3), max(4, 5), gt(7, 2))").unwrap();
+    assert_eq!(
+        p.pretty(24, true),
+        "sum(  # = 13\n  1,\n  prod(2, 3),  # = 6\n  max(4, 5),  # = 5\n  gt(7, 2)  # = 1\n)\n"
+    );
+    assert_eq!(p.pretty(80, false), format!("{}\n", p));
+
+    let mut seed = 5;
+    for _ in 0..200 {
+        let p = encode::random(&mut seed, 5);
+        assert_eq!(expr::compile(&p.to_string()).unwrap(), p);
+        for width in [0, 30, 200] {
+            assert_eq!(expr::compile(&p.pretty(width, true)).unwrap(), p);
+        }
+    }
+}
@@ -12,7 +12,7 @@ struct Grid {
 
 impl Grid {
     fn parse(f: impl BufRead) -> Self {
-        let mut l = f.lines().flatten();
+        let mut l = f.lines().map_while(Result::ok);
         let mapping = l.next().unwrap().chars().map(|s| s == '#').collect();
         l.next(); // ignore
 
@@ -46,14 +46,22 @@ impl Grid {
     }
 
     fn step(&mut self, n: usize) {
+        self.step_with(n, |_| {});
+    }
+
+    // calls `f` after every single step
+    fn step_with(&mut self, n: usize, mut f: impl FnMut(&Self)) {
         for _ in 0..n / 2 {
             self.expand(2);
             self.step_impl();
+            f(self);
             self.step_impl();
+            f(self);
         }
         if n % 2 == 1 {
             self.expand(1);
             self.step_impl();
+            f(self);
         }
     }
 
@@ -82,6 +90,12 @@ impl Grid {
     fn count(&self) -> usize {
         self.data.iter().filter(|&&c| c).count()
     }
+
+    fn frame(&self) -> animate::Frame {
+        animate::Frame::from_fn(self.cols, self.rows, |x, y| {
+            u8::from(self.data[y * self.cols + x])
+        })
+    }
 }
 
 impl fmt::Display for Grid {
@@ -103,6 +117,14 @@ impl fmt::Display for Grid {
 fn main() {
     let f = std::io::BufReader::new(std::io::stdin());
     let mut g = Grid::parse(f);
+    // day20 [animation flags]
+    if let (Some(out), _) = animate::Output::from_args(std::env::args().skip(1)) {
+        let mut a = animate::Animation::new(vec![[0, 0, 0], [255, 255, 255]]);
+        a.push(g.frame());
+        g.step_with(out.steps.unwrap_or(50), |g| a.push(g.frame()));
+        out.save(&a).unwrap();
+        return;
+    }
     let mut gg = g.clone();
     g.step(2);
     println!("{}", g.count());
@@ -121,3 +143,15 @@ fn test_grid() {
     g.step(48);
     assert_eq!(g.count(), 3351);
 }
+
+#[test]
+fn test_frames() {
+    let f = include_bytes!("../test/input.txt");
+    let mut g = Grid::parse(&f[..]);
+    let mut frames = vec![];
+    g.step_with(3, |g| frames.push(g.frame()));
+    assert_eq!(frames.len(), 3);
+    assert_eq!((frames[2].width, frames[2].height), (g.cols, g.rows));
+    let lit = frames[1].pixels.iter().filter(|&&p| p == 1).count();
+    assert_eq!(lit, 35);
+}
@@ -18,7 +18,7 @@ impl Map {
         let mut rows = 0;
         let mut cols = 0;
         let mut tiles = vec![];
-        for l in f.lines().flatten() {
+        for l in f.lines().map_while(Result::ok) {
             tiles.extend(l.chars().map(|c| match c {
                 '>' => Type::East,
                 '.' => Type::Empty,
@@ -80,6 +80,14 @@ impl Map {
     }
 }
 
+impl Map {
+    fn frame(&self) -> animate::Frame {
+        animate::Frame::from_fn(self.cols, self.rows, |x, y| {
+            self.tiles[y * self.cols + x] as u8
+        })
+    }
+}
+
 impl Debug for Map {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for r in 0..self.rows {
@@ -104,6 +112,20 @@ fn main() {
     let f = std::io::BufReader::new(std::io::stdin());
     let mut m = Map::parse(f);
 
+    // day25 [animation flags]
+    if let (Some(out), _) = animate::Output::from_args(std::env::args().skip(1)) {
+        let mut a = animate::Animation::new(vec![[10, 30, 70], [240, 140, 40], [60, 200, 120]]);
+        a.push(m.frame());
+        for _ in 0..out.steps.unwrap_or(usize::MAX) {
+            if m.step() == 0 {
+                break;
+            }
+            a.push(m.frame());
+        }
+        out.save(&a).unwrap();
+        return;
+    }
+
     for i in 1.. {
         if m.step() == 0 {
             println!("{}", i);
@@ -112,6 +134,14 @@ fn main() {
     }
 }
 
+#[test]
+fn test_frame() {
+    let m = Map::parse(">.v\n...".as_bytes());
+    let f = m.frame();
+    assert_eq!((f.width, f.height), (3, 2));
+    assert_eq!(f.pixels, vec![1, 0, 2, 0, 0, 0]);
+}
+
 #[test]
 fn test() {
     let mut m = Map::parse(